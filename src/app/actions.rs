@@ -14,11 +14,55 @@ pub enum Action {
     Forward,
     ToggleLog,
     ToggleHelp,
+    Yank,
+    Cut,
+    Paste,
+    ToggleJobs,
+    NextJob,
+    PauseJob,
+    CancelJob,
+    Trash,
+    DeleteForever,
+    ToggleTrash,
+    Restore,
+    PurgeTrashEntry,
+    EmptyTrash,
+    Undo,
+    Redo,
+    ToggleMark,
+    MarkRange,
+    MarkAll,
+    UnmarkAll,
+    InvertMarks,
+    MarkPattern,
+    UnmarkPattern,
+    Rename,
+    BatchRename,
+    EditNames,
+    NewFile,
+    NewDirectory,
+    NewFromTemplate,
+    SymlinkRelative,
+    SymlinkAbsolute,
+    HardLink,
+    JumpToLinkTarget,
+    Chmod,
+    Chown,
+    ToggleLongListing,
+    ToggleProperties,
+    Xattrs,
+    EditTags,
+    TagView,
+    EditNote,
+    SearchNotes,
+    Compress,
+    Extract,
+    ExtractTo,
 }
 
 impl Action {
     pub fn iterator() -> Iter<'static, Action> {
-        static ACTIONS: [Action; 8] = [
+        static ACTIONS: [Action; 52] = [
             Action::Quit,
             Action::Increment,
             Action::Decrement,
@@ -27,6 +71,50 @@ impl Action {
             Action::Forward,
             Action::ToggleLog,
             Action::ToggleHelp,
+            Action::Yank,
+            Action::Cut,
+            Action::Paste,
+            Action::ToggleJobs,
+            Action::NextJob,
+            Action::PauseJob,
+            Action::CancelJob,
+            Action::Trash,
+            Action::DeleteForever,
+            Action::ToggleTrash,
+            Action::Restore,
+            Action::PurgeTrashEntry,
+            Action::EmptyTrash,
+            Action::Undo,
+            Action::Redo,
+            Action::ToggleMark,
+            Action::MarkRange,
+            Action::MarkAll,
+            Action::UnmarkAll,
+            Action::InvertMarks,
+            Action::MarkPattern,
+            Action::UnmarkPattern,
+            Action::Rename,
+            Action::BatchRename,
+            Action::EditNames,
+            Action::NewFile,
+            Action::NewDirectory,
+            Action::NewFromTemplate,
+            Action::SymlinkRelative,
+            Action::SymlinkAbsolute,
+            Action::HardLink,
+            Action::JumpToLinkTarget,
+            Action::Chmod,
+            Action::Chown,
+            Action::ToggleLongListing,
+            Action::ToggleProperties,
+            Action::Xattrs,
+            Action::EditTags,
+            Action::TagView,
+            Action::EditNote,
+            Action::SearchNotes,
+            Action::Compress,
+            Action::Extract,
+            Action::ExtractTo,
         ];
         ACTIONS.iter()
     }
@@ -41,8 +129,98 @@ impl Action {
             Action::Forward => &[inputs::key::Key::Right],
             Action::ToggleLog => &[inputs::key::Key::Char('D')],
             Action::ToggleHelp => &[inputs::key::Key::Char('?')],
+            Action::Yank => &[inputs::key::Key::Char('y')],
+            Action::Cut => &[inputs::key::Key::Char('x')],
+            Action::Paste => &[inputs::key::Key::Char('p')],
+            Action::ToggleJobs => &[inputs::key::Key::Char('J')],
+            Action::NextJob => &[inputs::key::Key::Tab],
+            Action::PauseJob => &[inputs::key::Key::Char('P')],
+            Action::CancelJob => &[inputs::key::Key::Char('C')],
+            Action::Trash => &[inputs::key::Key::Char('d'), inputs::key::Key::Delete],
+            Action::DeleteForever => &[inputs::key::Key::Ctrl('d')],
+            Action::ToggleTrash => &[inputs::key::Key::Char('T')],
+            Action::Restore => &[inputs::key::Key::Enter, inputs::key::Key::Char('r')],
+            Action::PurgeTrashEntry => &[inputs::key::Key::Char('d'), inputs::key::Key::Delete],
+            Action::EmptyTrash => &[inputs::key::Key::Char('E')],
+            Action::Undo => &[inputs::key::Key::Char('u')],
+            Action::Redo => &[inputs::key::Key::Ctrl('r')],
+            Action::ToggleMark => &[inputs::key::Key::Char(' ')],
+            Action::MarkRange => &[inputs::key::Key::Char('v')],
+            Action::MarkAll => &[inputs::key::Key::Char('a')],
+            Action::UnmarkAll => &[inputs::key::Key::Char('A')],
+            Action::InvertMarks => &[inputs::key::Key::Char('i')],
+            Action::MarkPattern => &[inputs::key::Key::Char('+')],
+            Action::UnmarkPattern => &[inputs::key::Key::Char('-')],
+            Action::Rename => &[inputs::key::Key::Char('r'), inputs::key::Key::F2],
+            Action::BatchRename => &[inputs::key::Key::Char('R')],
+            Action::EditNames => &[inputs::key::Key::Char('e')],
+            Action::NewFile => &[inputs::key::Key::Char('n')],
+            Action::NewDirectory => &[inputs::key::Key::Char('N')],
+            Action::NewFromTemplate => &[inputs::key::Key::Ctrl('n')],
+            Action::SymlinkRelative => &[inputs::key::Key::Char('s')],
+            Action::SymlinkAbsolute => &[inputs::key::Key::Char('S')],
+            Action::HardLink => &[inputs::key::Key::Char('H')],
+            Action::JumpToLinkTarget => &[inputs::key::Key::Char('g')],
+            Action::Chmod => &[inputs::key::Key::Char('c')],
+            Action::Chown => &[inputs::key::Key::Char('o')],
+            Action::ToggleLongListing => &[inputs::key::Key::Char('L')],
+            Action::ToggleProperties => &[inputs::key::Key::Char('I')],
+            Action::Xattrs => &[inputs::key::Key::Char('X')],
+            Action::EditTags => &[inputs::key::Key::Char('t')],
+            Action::TagView => &[inputs::key::Key::Char('G')],
+            Action::EditNote => &[inputs::key::Key::Char('m')],
+            Action::SearchNotes => &[inputs::key::Key::Char('F')],
+            Action::Compress => &[inputs::key::Key::Char('z')],
+            Action::Extract => &[inputs::key::Key::Char('Z')],
+            Action::ExtractTo => &[inputs::key::Key::Ctrl('x')],
         }
     }
+
+    /// Whether the action changes entries in the cursor directory or the selected
+    /// ones, refused inside archives whose contents are a read-only copy
+    pub fn modifies_entries(&self) -> bool {
+        matches!(
+            self,
+            Action::Cut
+                | Action::Paste
+                | Action::Trash
+                | Action::DeleteForever
+                | Action::Rename
+                | Action::BatchRename
+                | Action::EditNames
+                | Action::NewFile
+                | Action::NewDirectory
+                | Action::NewFromTemplate
+                | Action::SymlinkRelative
+                | Action::SymlinkAbsolute
+                | Action::HardLink
+                | Action::Chmod
+                | Action::Chown
+                | Action::Xattrs
+                | Action::EditTags
+                | Action::EditNote
+                | Action::Compress
+                | Action::Extract
+                | Action::ExtractTo
+        )
+    }
+
+    /// Whether the action works on real paths rather than through the `Vfs`,
+    /// refused when browsing anything but the local disk
+    pub fn needs_local_fs(&self) -> bool {
+        matches!(
+            self,
+            Action::NewFromTemplate
+                | Action::Xattrs
+                | Action::EditTags
+                | Action::TagView
+                | Action::EditNote
+                | Action::SearchNotes
+                | Action::Compress
+                | Action::Extract
+                | Action::ExtractTo
+        )
+    }
 }
 
 impl Display for Action {
@@ -53,9 +231,53 @@ impl Display for Action {
             Action::Decrement => "Select Previous",
             Action::Select => "Select",
             Action::Back => "Cursor Go To Parent",
-            Action::Forward => "Cursor Go To Selected Directory",
+            Action::Forward => "Cursor Go To Selected Directory Or Archive",
             Action::ToggleLog => "Toggle Log",
             Action::ToggleHelp => "Toggle Help",
+            Action::Yank => "Copy Marked Or Selected",
+            Action::Cut => "Cut Marked Or Selected",
+            Action::Paste => "Paste Into Cursor Directory",
+            Action::ToggleJobs => "Toggle Jobs",
+            Action::NextJob => "Select Next Job",
+            Action::PauseJob => "Pause/Resume Selected Job",
+            Action::CancelJob => "Cancel Selected Job",
+            Action::Trash => "Move Marked Or Selected To Trash",
+            Action::DeleteForever => "Delete Marked Or Selected Permanently",
+            Action::ToggleTrash => "Toggle Trash View",
+            Action::Restore => "Restore To Original Location",
+            Action::PurgeTrashEntry => "Delete From Trash Permanently",
+            Action::EmptyTrash => "Empty Trash",
+            Action::Undo => "Undo Last File Operation",
+            Action::Redo => "Redo Last Undone File Operation",
+            Action::ToggleMark => "Toggle Mark On Selected",
+            Action::MarkRange => "Mark From Last Toggled To Selected",
+            Action::MarkAll => "Mark All In Directory",
+            Action::UnmarkAll => "Unmark Everything",
+            Action::InvertMarks => "Invert Marks In Directory",
+            Action::MarkPattern => "Mark By Glob Or Regex",
+            Action::UnmarkPattern => "Unmark By Glob Or Regex",
+            Action::Rename => "Rename Selected",
+            Action::BatchRename => "Batch Rename Marked",
+            Action::EditNames => "Rename In Editor",
+            Action::NewFile => "New File",
+            Action::NewDirectory => "New Directory",
+            Action::NewFromTemplate => "New From Template",
+            Action::SymlinkRelative => "Symlink Marked Here (Relative)",
+            Action::SymlinkAbsolute => "Symlink Marked Here (Absolute)",
+            Action::HardLink => "Hard Link Marked Here",
+            Action::JumpToLinkTarget => "Go To Link Target",
+            Action::Chmod => "Change Permissions",
+            Action::Chown => "Change Owner",
+            Action::ToggleLongListing => "Toggle Long Listing",
+            Action::ToggleProperties => "Toggle Properties",
+            Action::Xattrs => "Extended Attributes",
+            Action::EditTags => "Edit Tags",
+            Action::TagView => "Browse By Tag",
+            Action::EditNote => "Edit Note",
+            Action::SearchNotes => "Search Notes",
+            Action::Compress => "Compress",
+            Action::Extract => "Extract Here",
+            Action::ExtractTo => "Extract To",
         };
         write!(f, "{}", str)
     }
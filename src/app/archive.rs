@@ -0,0 +1,529 @@
+//! Zip, tar, tar.gz and tar.zst archives, the format going by the extension.
+//!
+//! Both directions report to a `JobProgress` and stop at its pauses and
+//! cancellation. Extraction refuses entries that would land outside the
+//! destination, whether by name (`../x`, `/x`) or through a symlink an earlier
+//! entry put in place.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::Metadata;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::sync::atomic::Ordering;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::warn;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::app::config::cache_home;
+use crate::app::jobs::JobProgress;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+/// Longer extensions first so `.tar.gz` isn't taken for something else
+const EXTENSIONS: [(&str, ArchiveFormat); 6] = [
+    (".tar.gz", ArchiveFormat::TarGz),
+    (".tgz", ArchiveFormat::TarGz),
+    (".tar.zst", ArchiveFormat::TarZst),
+    (".tzst", ArchiveFormat::TarZst),
+    (".tar", ArchiveFormat::Tar),
+    (".zip", ArchiveFormat::Zip),
+];
+
+impl ArchiveFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        split_name(path).map(|(_, format)| format)
+    }
+}
+
+/// Name of `path` without its archive extension, and the format it names
+fn split_name(path: &Path) -> Option<(String, ArchiveFormat)> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let lower = name.to_lowercase();
+
+    EXTENSIONS.iter()
+        .find(|(extension, _)| lower.len() > extension.len() && lower.ends_with(extension))
+        .map(|(extension, format)| (name[..name.len() - extension.len()].to_string(), *format))
+}
+
+/// Directory holding the contents of `archive` for browsing, filled by `unpack_for_browsing`.
+///
+/// The directory is private to this process and keyed by the archive's path,
+/// size and modification time, so a changed archive is extracted again.
+pub fn browse_dir(archive: &Path) -> io::Result<PathBuf> {
+    let metadata = archive.metadata()?;
+
+    let mut hasher = DefaultHasher::new();
+    (archive, metadata.len(), metadata.modified()?).hash(&mut hasher);
+
+    Ok(browse_cache().join(format!("{:016x}", hasher.finish())))
+}
+
+/// Extract `archive` to `dir`, its `browse_dir`, which only exists once complete
+pub fn unpack_for_browsing(archive: &Path, dir: &Path, progress: &JobProgress) -> io::Result<()> {
+    // only a complete extraction gets the real name, so a failed one is never reused
+    let partial = dir.with_extension("partial");
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+
+    let rejected = match extract(archive, &partial, progress) {
+        Ok(extracted) => extracted.rejected,
+        Err(err) => {
+            let _ = fs::remove_dir_all(&partial);
+            return Err(err);
+        }
+    };
+    fs::rename(&partial, dir)?;
+
+    if rejected > 0 {
+        warn!("{} entr(ies) of {} point outside it and are not shown", rejected, archive.display());
+    }
+
+    Ok(())
+}
+
+/// Where archives being browsed are extracted, removed by `clear_browse_cache`
+fn browse_cache() -> PathBuf {
+    cache_home().join("file_tui").join("archives").join(process::id().to_string())
+}
+
+pub fn clear_browse_cache() {
+    let cache = browse_cache();
+    if cache.exists() {
+        if let Err(err) = fs::remove_dir_all(&cache) {
+            warn!("Cannot remove {}: {}", cache.display(), err);
+        }
+    }
+}
+
+/// Archive being extracted in the background, entered once its job finishes
+pub struct PendingBrowse {
+    pub job: usize,
+    pub archive: PathBuf,
+    pub root: PathBuf,
+}
+
+/// Sibling directory the archive extracts to by default
+pub fn default_destination(archive: &Path) -> Option<PathBuf> {
+    let (stem, _) = split_name(archive)?;
+    Some(archive.with_file_name(stem))
+}
+
+/// Counts what is read towards the job progress
+struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a JobProgress,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.progress.checkpoint()?;
+
+        let read = self.inner.read(buf)?;
+        self.progress.bytes_done.fetch_add(read as u64, Ordering::Relaxed);
+
+        Ok(read)
+    }
+}
+
+/// An entry on disk with the name it gets in the archive
+struct Entry {
+    path: PathBuf,
+    name: PathBuf,
+    metadata: Metadata,
+}
+
+/// Pack `sources` into a new `archive`, naming entries relative to `base`.
+///
+/// A half written archive is removed.
+pub fn compress(base: &Path, sources: &[PathBuf], archive: &Path, progress: &JobProgress) -> io::Result<()> {
+    let format = ArchiveFormat::from_path(archive)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a known archive type", archive.display())))?;
+
+    let mut entries = Vec::new();
+    for source in sources {
+        let name = match source.strip_prefix(base) {
+            Ok(name) => name.to_path_buf(),
+            Err(_) => PathBuf::from(source.file_name().unwrap_or_default()),
+        };
+        collect(source, name, &mut entries, progress)?;
+    }
+    progress.scanned.store(true, Ordering::Relaxed);
+
+    let file = fs::OpenOptions::new().write(true).create_new(true).open(archive)?;
+    let result = match format {
+        ArchiveFormat::Zip => write_zip(file, &entries, progress),
+        ArchiveFormat::Tar => write_tar(file, &entries, progress).map(|_| ()),
+        ArchiveFormat::TarGz => write_tar(GzEncoder::new(file, Compression::default()), &entries, progress)
+            .and_then(GzEncoder::finish)
+            .map(|_| ()),
+        ArchiveFormat::TarZst => write_tar(zstd::Encoder::new(file, 0)?, &entries, progress)
+            .and_then(zstd::Encoder::finish)
+            .map(|_| ()),
+    };
+
+    if result.is_err() {
+        let _ = fs::remove_file(archive);
+    }
+
+    result
+}
+
+/// Every entry of the tree at `path`, parents before their children
+fn collect(path: &Path, name: PathBuf, entries: &mut Vec<Entry>, progress: &JobProgress) -> io::Result<()> {
+    progress.checkpoint()?;
+    let metadata = path.symlink_metadata()?;
+
+    if metadata.is_file() {
+        progress.files_total.fetch_add(1, Ordering::Relaxed);
+        progress.bytes_total.fetch_add(metadata.len(), Ordering::Relaxed);
+    }
+
+    let is_dir = metadata.is_dir();
+    entries.push(Entry { path: path.to_path_buf(), name: name.clone(), metadata });
+
+    if is_dir {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            collect(&entry.path(), name.join(entry.file_name()), entries, progress)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_tar<W: Write>(writer: W, entries: &[Entry], progress: &JobProgress) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+
+    for entry in entries {
+        progress.checkpoint()?;
+
+        if entry.metadata.is_file() {
+            // the size must be the one of the file actually read, and exactly that many
+            // bytes written even if it changes meanwhile, or the rest of the archive is garbage
+            let file = fs::File::open(&entry.path)?;
+            let metadata = file.metadata()?;
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata(&metadata);
+
+            let reader = ProgressReader { inner: file, progress }
+                .take(metadata.len())
+                .chain(io::repeat(0))
+                .take(metadata.len());
+            builder.append_data(&mut header, &entry.name, reader)?;
+            progress.files_done.fetch_add(1, Ordering::Relaxed);
+        }
+        else {
+            builder.append_path_with_name(&entry.path, &entry.name)?;
+        }
+    }
+
+    builder.into_inner()
+}
+
+fn write_zip(file: fs::File, entries: &[Entry], progress: &JobProgress) -> io::Result<()> {
+    let mut zip = ZipWriter::new(file);
+
+    for entry in entries {
+        progress.checkpoint()?;
+
+        let name = entry.name.to_string_lossy().to_string();
+        let options = SimpleFileOptions::default().unix_permissions(entry.metadata.mode() & 0o7777);
+        let file_type = entry.metadata.file_type();
+
+        if file_type.is_symlink() {
+            let target = fs::read_link(&entry.path)?;
+            zip.add_symlink(name, target.to_string_lossy(), options).map_err(io::Error::other)?;
+        }
+        else if file_type.is_dir() {
+            zip.add_directory(name, options).map_err(io::Error::other)?;
+        }
+        else if file_type.is_file() {
+            let options = options
+                .compression_method(CompressionMethod::Deflated)
+                .large_file(entry.metadata.len() >= u32::MAX as u64);
+            zip.start_file(name, options).map_err(io::Error::other)?;
+
+            let mut reader = ProgressReader { inner: fs::File::open(&entry.path)?, progress };
+            io::copy(&mut reader, &mut zip)?;
+            progress.files_done.fetch_add(1, Ordering::Relaxed);
+        }
+        else {
+            warn!("Zip cannot hold {}, left out", entry.path.display());
+        }
+    }
+
+    zip.finish().map_err(io::Error::other)?;
+
+    Ok(())
+}
+
+/// Unpack `archive` into `dest`, creating it if needed.
+///
+/// Existing files are never overwritten. Entries that would escape `dest` are
+/// skipped, returns how many there were.
+pub fn extract(archive: &Path, dest: &Path, progress: &JobProgress) -> io::Result<Extracted> {
+    let format = ArchiveFormat::from_path(archive)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a known archive type", archive.display())))?;
+
+    fs::create_dir_all(dest)?;
+
+    let extracted = match format {
+        ArchiveFormat::Zip => extract_zip(archive, dest, progress)?,
+        ArchiveFormat::Tar | ArchiveFormat::TarGz | ArchiveFormat::TarZst => {
+            // tar streams have no index, progress goes by the compressed bytes read
+            let file = fs::File::open(archive)?;
+            progress.bytes_total.store(file.metadata()?.len(), Ordering::Relaxed);
+            progress.scanned.store(true, Ordering::Relaxed);
+
+            let reader = ProgressReader { inner: file, progress };
+            match format {
+                ArchiveFormat::TarGz => extract_tar(GzDecoder::new(reader), dest, progress)?,
+                ArchiveFormat::TarZst => extract_tar(zstd::Decoder::new(reader)?, dest, progress)?,
+                _ => extract_tar(reader, dest, progress)?,
+            }
+        }
+    };
+
+    Ok(extracted)
+}
+
+/// Entries of an extraction that were left out
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Extracted {
+    /// Pointing outside the destination
+    pub rejected: usize,
+    /// Already in the destination and kept as they were
+    pub skipped: usize,
+}
+
+fn extract_tar<R: Read>(reader: R, dest: &Path, progress: &JobProgress) -> io::Result<Extracted> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_overwrite(false);
+
+    let mut extracted = Extracted::default();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_path_buf();
+        progress.files_total.fetch_add(1, Ordering::Relaxed);
+
+        let path = match entry_path(dest, &name) {
+            Some(path) => path,
+            None => {
+                warn!("Not extracting '{}', it points outside {}", name.display(), dest.display());
+                extracted.rejected += 1;
+                continue;
+            }
+        };
+
+        if let Ok(metadata) = path.symlink_metadata() {
+            if !(metadata.is_dir() && entry.header().entry_type().is_dir()) {
+                skip_existing(&name, &path, &mut extracted);
+            }
+            // an existing directory is extracted into, its mode left alone
+            progress.files_done.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // unpack_in also checks hard link targets against dest, refusing with false
+        if entry.unpack_in(dest)? {
+            progress.files_done.fetch_add(1, Ordering::Relaxed);
+        }
+        else {
+            warn!("Not extracting '{}', it points outside {}", name.display(), dest.display());
+            extracted.rejected += 1;
+        }
+    }
+
+    Ok(extracted)
+}
+
+fn skip_existing(name: &Path, path: &Path, extracted: &mut Extracted) {
+    warn!("Not extracting '{}', {} already exists", name.display(), path.display());
+    extracted.skipped += 1;
+}
+
+fn extract_zip(archive: &Path, dest: &Path, progress: &JobProgress) -> io::Result<Extracted> {
+    let mut zip = ZipArchive::new(fs::File::open(archive)?).map_err(io::Error::other)?;
+
+    for index in 0..zip.len() {
+        let file = zip.by_index_raw(index).map_err(io::Error::other)?;
+        progress.files_total.fetch_add(1, Ordering::Relaxed);
+        progress.bytes_total.fetch_add(file.size(), Ordering::Relaxed);
+    }
+    progress.scanned.store(true, Ordering::Relaxed);
+
+    let mut extracted = Extracted::default();
+    // applied last so a read-only directory doesn't block its own entries
+    let mut dir_modes = HashMap::new();
+
+    for index in 0..zip.len() {
+        progress.checkpoint()?;
+
+        let mut file = zip.by_index(index).map_err(io::Error::other)?;
+        let name = PathBuf::from(file.name());
+
+        let path = match entry_path(dest, &name) {
+            Some(path) => path,
+            None => {
+                warn!("Not extracting '{}', it points outside {}", name.display(), dest.display());
+                extracted.rejected += 1;
+                continue;
+            }
+        };
+
+        let existing = path.symlink_metadata().ok();
+
+        if file.is_dir() {
+            match existing {
+                // an existing directory is extracted into, its mode left alone
+                Some(metadata) if metadata.is_dir() => {}
+                Some(_) => skip_existing(&name, &path, &mut extracted),
+                None => {
+                    fs::create_dir_all(&path)?;
+                    if let Some(mode) = file.unix_mode() {
+                        dir_modes.insert(path, mode & 0o777);
+                    }
+                }
+            }
+            progress.files_done.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+
+        if existing.is_some() {
+            skip_existing(&name, &path, &mut extracted);
+            progress.bytes_done.fetch_add(file.size(), Ordering::Relaxed);
+            progress.files_done.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if file.is_symlink() {
+            let mut target = String::new();
+            file.read_to_string(&mut target)?;
+            symlink(target, &path)?;
+        }
+        else {
+            let mut out = fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+            io::copy(&mut ProgressReader { inner: &mut file, progress }, &mut out)?;
+
+            if let Some(mode) = file.unix_mode() {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+            }
+        }
+
+        progress.files_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    for (path, mode) in dir_modes {
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(extracted)
+}
+
+/// Where an entry called `name` goes inside `dest`, `None` when it would end up
+/// outside: an absolute name, a `..` component, or a parent that is a symlink.
+/// A `./` entry is `dest` itself.
+fn entry_path(dest: &Path, name: &Path) -> Option<PathBuf> {
+    let mut path = dest.to_path_buf();
+
+    for component in name.components() {
+        // checked before descending, the entry itself may well be a symlink
+        if path != dest && path.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            return None;
+        }
+
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn destination_drops_the_archive_extension() {
+        assert_eq!(default_destination(Path::new("/a/photos.tar.gz")), Some(PathBuf::from("/a/photos")));
+        assert_eq!(default_destination(Path::new("/a/Backup.ZIP")), Some(PathBuf::from("/a/Backup")));
+        assert_eq!(default_destination(Path::new("/a/.zip")), None);
+        assert_eq!(default_destination(Path::new("/a/notes.txt")), None);
+    }
+
+    #[test]
+    fn entry_paths_stay_inside_the_destination() {
+        let dest = Path::new("/nonexistent/dest");
+
+        assert_eq!(entry_path(dest, Path::new("a/b.txt")), Some(dest.join("a/b.txt")));
+        assert_eq!(entry_path(dest, Path::new("./a")), Some(dest.join("a")));
+        assert_eq!(entry_path(dest, Path::new("./")), Some(dest.to_path_buf()));
+        assert_eq!(entry_path(dest, Path::new("../a")), None);
+        assert_eq!(entry_path(dest, Path::new("a/../../b")), None);
+        assert_eq!(entry_path(dest, Path::new("/etc/passwd")), None);
+    }
+
+    #[test]
+    fn tar_round_trip_keeps_the_content() {
+        let dir = std::env::temp_dir().join(format!("file_tui-tar-test-{}", process::id()));
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::write(dir.join("src/a.txt"), "first").unwrap();
+        fs::write(dir.join("src/sub/b.txt"), vec![7; 70_000]).unwrap();
+
+        let archive = dir.join("src.tar.gz");
+        let result = compress(&dir, &[dir.join("src")], &archive, &JobProgress::default())
+            .and_then(|_| extract(&archive, &dir.join("out"), &JobProgress::default()));
+        let a = fs::read(dir.join("out/src/a.txt"));
+        let b = fs::read(dir.join("out/src/sub/b.txt"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(a.unwrap(), b"first");
+        assert_eq!(b.unwrap(), vec![7; 70_000]);
+    }
+
+    #[test]
+    fn entry_paths_never_go_through_a_symlink() {
+        let dest = std::env::temp_dir().join(format!("file_tui-archive-test-{}", process::id()));
+        fs::create_dir_all(dest.join("dir")).unwrap();
+        symlink("/tmp", dest.join("link")).unwrap();
+
+        let inside = entry_path(&dest, Path::new("dir/file"));
+        let link = entry_path(&dest, Path::new("link"));
+        let through_link = entry_path(&dest, Path::new("link/file"));
+        fs::remove_dir_all(&dest).unwrap();
+
+        assert_eq!(inside, Some(dest.join("dir/file")));
+        assert_eq!(link, Some(dest.join("link")));
+        assert_eq!(through_link, None);
+    }
+}
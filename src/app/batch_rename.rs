@@ -0,0 +1,278 @@
+//! Renaming many entries at once from a regex, a counter, a case change and a new extension.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use regex::{Captures, Regex};
+
+use crate::app::vfs::Vfs;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CaseConversion {
+    Keep,
+    Lower,
+    Upper,
+    Title,
+}
+
+impl CaseConversion {
+    pub fn next(&self) -> Self {
+        match self {
+            CaseConversion::Keep => CaseConversion::Lower,
+            CaseConversion::Lower => CaseConversion::Upper,
+            CaseConversion::Upper => CaseConversion::Title,
+            CaseConversion::Title => CaseConversion::Keep,
+        }
+    }
+
+    pub fn previous(&self) -> Self {
+        match self {
+            CaseConversion::Keep => CaseConversion::Title,
+            CaseConversion::Lower => CaseConversion::Keep,
+            CaseConversion::Upper => CaseConversion::Lower,
+            CaseConversion::Title => CaseConversion::Upper,
+        }
+    }
+
+    fn apply(&self, text: &str) -> String {
+        match self {
+            CaseConversion::Keep => text.to_string(),
+            CaseConversion::Lower => text.to_lowercase(),
+            CaseConversion::Upper => text.to_uppercase(),
+            CaseConversion::Title => {
+                let mut title = String::with_capacity(text.len());
+                let mut word_start = true;
+
+                for c in text.chars() {
+                    if word_start {
+                        title.extend(c.to_uppercase());
+                    }
+                    else {
+                        title.extend(c.to_lowercase());
+                    }
+
+                    word_start = !c.is_alphanumeric();
+                }
+
+                title
+            }
+        }
+    }
+}
+
+impl Display for CaseConversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let str = match self {
+            CaseConversion::Keep => "Keep",
+            CaseConversion::Lower => "lower",
+            CaseConversion::Upper => "UPPER",
+            CaseConversion::Title => "Title",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+/// Settings of a batch rename as typed in the dialog
+pub struct BatchRenameOptions<'a> {
+    /// Regex matched against the whole name
+    pub find: &'a str,
+    /// Replacement for the first match, `$1` and `${name}` expand captures, `{n}` the counter
+    pub replace: &'a str,
+    pub start: &'a str,
+    /// Minimum digits of the counter, zero padded
+    pub padding: &'a str,
+    /// Applied to the name without its extension
+    pub case: CaseConversion,
+    /// Replaces the extension when not empty
+    pub extension: &'a str,
+}
+
+pub struct RenameRow {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Why this row can't be renamed
+    pub problem: Option<String>,
+}
+
+impl RenameRow {
+    pub fn is_unchanged(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/// Work out the new name of every source without changing anything, `vfs` is
+/// only asked which names are taken
+pub fn preview(vfs: &dyn Vfs, sources: &[PathBuf], options: &BatchRenameOptions) -> Result<Vec<RenameRow>, String> {
+    let find = if options.find.is_empty() { "^.*$" } else { options.find };
+    let regex = Regex::new(find).map_err(|err| err.to_string())?;
+    let start: u64 = parse_number(options.start, 1, "Start")?;
+    let padding: usize = parse_number(options.padding, 0, "Padding")?;
+
+    let mut rows = Vec::new();
+    for (index, from) in sources.iter().enumerate() {
+        let name = from.file_name().unwrap_or_default().to_string_lossy();
+        let counter = format!("{:0width$}", start + index as u64, width = padding);
+
+        // captures are expanded around the counter, so a literal `{n}` they bring in stays
+        let replaced = regex.replace(&name, |captures: &Captures| {
+            options.replace.split("{n}")
+                .map(|part| {
+                    let mut expanded = String::new();
+                    captures.expand(part, &mut expanded);
+                    expanded
+                })
+                .collect::<Vec<_>>()
+                .join(&counter)
+        });
+        let renamed = convert(&replaced, options.case, options.extension.trim_start_matches('.'));
+
+        rows.push(RenameRow {
+            from: from.clone(),
+            to: from.with_file_name(&renamed),
+            problem: check_name(&renamed),
+        });
+    }
+
+    find_collisions(vfs, &mut rows);
+
+    Ok(rows)
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, default: T, field: &str) -> Result<T, String> {
+    if text.trim().is_empty() {
+        return Ok(default);
+    }
+
+    text.trim().parse().map_err(|_| format!("{} must be a number", field))
+}
+
+/// Case applies to the stem only, then the extension is swapped
+fn convert(name: &str, case: CaseConversion, extension: &str) -> String {
+    let path = Path::new(name);
+    let (stem, old_extension) = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => (stem.to_string_lossy().to_string(), Some(ext.to_string_lossy().to_string())),
+        _ => (name.to_string(), None),
+    };
+
+    let stem = case.apply(&stem);
+    let extension = if extension.is_empty() { old_extension } else { Some(extension.to_string()) };
+
+    match extension {
+        Some(extension) => format!("{}.{}", stem, extension),
+        None => stem,
+    }
+}
+
+fn check_name(name: &str) -> Option<String> {
+    if name.is_empty() || name == "." || name == ".." {
+        Some(String::from("empty name"))
+    }
+    else if name.contains('/') {
+        Some(String::from("name contains '/'"))
+    }
+    else {
+        None
+    }
+}
+
+/// Flag rows that would land on the same path, or on an existing entry outside the batch
+fn find_collisions(vfs: &dyn Vfs, rows: &mut [RenameRow]) {
+    let sources: HashSet<PathBuf> = rows.iter().map(|row| row.from.clone()).collect();
+    let mut targets = HashSet::new();
+
+    for row in rows.iter_mut() {
+        if row.problem.is_some() || row.is_unchanged() {
+            continue;
+        }
+
+        if !targets.insert(row.to.clone()) {
+            row.problem = Some(String::from("same name as another entry"));
+        }
+        else if sources.contains(&row.to) {
+            // renames run one by one, so even a swap inside the batch would clobber
+            row.problem = Some(String::from("name taken by another entry in the batch"));
+        }
+        else if vfs.symlink_stat(&row.to).is_ok() {
+            row.problem = Some(String::from("already exists"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::vfs::MemoryFs;
+
+    fn options<'a>(find: &'a str, replace: &'a str) -> BatchRenameOptions<'a> {
+        BatchRenameOptions { find, replace, start: "", padding: "", case: CaseConversion::Keep, extension: "" }
+    }
+
+    fn renamed(fixture: &str, options: &BatchRenameOptions) -> Vec<String> {
+        let vfs = MemoryFs::from_fixture(fixture).unwrap();
+        let sources: Vec<PathBuf> = fixture.lines()
+            .map(|line| Path::new("/").join(line.trim()))
+            .collect();
+
+        preview(&vfs, &sources, options).unwrap()
+            .into_iter()
+            .map(|row| row.to.file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn counter_is_padded_from_start() {
+        let mut options = options(r"^.*\.(\w+)$", "photo-{n}.$1");
+        options.start = "9";
+        options.padding = "3";
+
+        assert_eq!(renamed("a.jpg\nb.png", &options), ["photo-009.jpg", "photo-010.png"]);
+    }
+
+    #[test]
+    fn literal_counter_from_a_capture_stays() {
+        let options = options(r"^(.*)-old$", "$1-{n}");
+
+        assert_eq!(renamed("{n}-old\nx-old", &options), ["{n}-1", "x-2"]);
+    }
+
+    #[test]
+    fn case_applies_to_the_stem_only() {
+        let mut options = options("", "$0");
+        options.case = CaseConversion::Title;
+
+        assert_eq!(renamed("my_holiday-pics.JPG", &options), ["My_Holiday-Pics.JPG"]);
+
+        options.case = CaseConversion::Upper;
+        options.extension = ".txt";
+        assert_eq!(renamed("notes.md\nREADME", &options), ["NOTES.txt", "README.txt"]);
+    }
+
+    #[test]
+    fn collisions_and_bad_names_are_flagged() {
+        let vfs = MemoryFs::from_fixture("a\nb\ntaken").unwrap();
+        let sources = [PathBuf::from("/a"), PathBuf::from("/b")];
+
+        let rows = preview(&vfs, &sources, &options("", "same")).unwrap();
+        assert_eq!(rows[0].problem, None);
+        assert_eq!(rows[1].problem.as_deref(), Some("same name as another entry"));
+
+        let rows = preview(&vfs, &sources[..1], &options("", "taken")).unwrap();
+        assert_eq!(rows[0].problem.as_deref(), Some("already exists"));
+
+        let rows = preview(&vfs, &sources[..1], &options("", "x/y")).unwrap();
+        assert_eq!(rows[0].problem.as_deref(), Some("name contains '/'"));
+    }
+
+    #[test]
+    fn bad_numbers_and_regexes_are_errors() {
+        let vfs = MemoryFs::from_fixture("").unwrap();
+        let mut options = options("(", "");
+        assert!(preview(&vfs, &[], &options).is_err());
+
+        options.find = "";
+        options.start = "one";
+        assert_eq!(preview(&vfs, &[], &options).err().as_deref(), Some("Start must be a number"));
+    }
+}
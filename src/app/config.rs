@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::PathBuf;
+
+use log::warn;
+
+use crate::app::conflict::ConflictPolicy;
+
+/// `$XDG_CONFIG_HOME/file_tui`, defaulting to `~/.config/file_tui`
+pub fn config_dir() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config").join("file_tui")
+}
+
+/// Skeleton files offered by "new from template"
+pub fn templates_dir() -> PathBuf {
+    config_dir().join("templates")
+}
+
+/// `$XDG_DATA_HOME`, defaulting to `~/.local/share`
+pub fn data_home() -> PathBuf {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// `$XDG_CACHE_HOME`, defaulting to `~/.cache`
+pub fn cache_home() -> PathBuf {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$XDG_STATE_HOME/file_tui`, defaulting to `~/.local/state/file_tui`
+pub fn state_dir() -> PathBuf {
+    xdg_dir("XDG_STATE_HOME", ".local/state").join("file_tui")
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    match std::env::var_os(var) {
+        // the spec says relative values must be ignored
+        Some(dir) if PathBuf::from(&dir).is_absolute() => PathBuf::from(dir),
+        _ => home_dir().join(fallback),
+    }
+}
+
+fn home_dir() -> PathBuf {
+    PathBuf::from(std::env::var_os("HOME").unwrap_or_default())
+}
+
+/// `~/x` as `$HOME/x`
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) => home_dir().join(rest.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+/// User settings read from `config_dir()/config`, one `key = value` per line
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub conflict_policy: ConflictPolicy,
+    /// Copy extended attributes along with the data when copying or moving across filesystems
+    pub preserve_xattrs: bool,
+    /// Directories searched by the tag view, `:` separated in the config file
+    pub tag_roots: Vec<PathBuf>,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut config = Self::default();
+
+        let content = match fs::read_to_string(config_dir().join("config")) {
+            Ok(content) => content,
+            Err(_) => return config,
+        };
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    warn!("Config: ignoring malformed line '{}'", line);
+                    continue;
+                }
+            };
+
+            match key {
+                "conflict_policy" => match value.parse() {
+                    Ok(policy) => config.conflict_policy = policy,
+                    Err(_) => warn!("Config: unknown conflict_policy '{}'", value),
+                },
+                "tag_roots" => {
+                    config.tag_roots = value.split(':')
+                        .filter(|root| !root.trim().is_empty())
+                        .map(|root| expand_home(root.trim()))
+                        .collect();
+                }
+                "preserve_xattrs" => match value.parse() {
+                    Ok(preserve) => config.preserve_xattrs = preserve,
+                    Err(_) => warn!("Config: preserve_xattrs must be true or false, not '{}'", value),
+                },
+                _ => warn!("Config: unknown key '{}'", key),
+            }
+        }
+
+        config
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            conflict_policy: ConflictPolicy::Ask,
+            preserve_xattrs: false,
+            tag_roots: vec![home_dir()],
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::app::jobs::Transfer;
+use crate::app::vfs::Vfs;
+
+/// What to do with one transfer whose target already exists
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Resolution {
+    Overwrite,
+    Skip,
+    Rename,
+    OverwriteIfNewer,
+}
+
+impl Resolution {
+    /// Apply the resolution, returning the transfer to run or `None` to skip it
+    pub fn resolve(&self, vfs: &dyn Vfs, transfer: Transfer) -> Option<Transfer> {
+        // overwriting an entry with itself would destroy the source
+        let same_path = transfer.from == transfer.to;
+
+        match self {
+            Resolution::Skip => None,
+            Resolution::Overwrite if same_path => None,
+            Resolution::Overwrite => Some(Transfer { overwrite: true, ..transfer }),
+            Resolution::Rename => Some(Transfer { to: free_name(vfs, &transfer.to), ..transfer }),
+            Resolution::OverwriteIfNewer => {
+                if !same_path && is_newer(vfs, &transfer.from, &transfer.to) {
+                    Some(Transfer { overwrite: true, ..transfer })
+                }
+                else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let str = match self {
+            Resolution::Overwrite => "Overwrite",
+            Resolution::Skip => "Skip",
+            Resolution::Rename => "Rename",
+            Resolution::OverwriteIfNewer => "Overwrite If Newer",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+/// How conflicts are settled when a paste runs
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ConflictPolicy {
+    /// Prompt for every conflict
+    Ask,
+    /// Settle every conflict the same way without prompting
+    Always(Resolution),
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ask" => Ok(ConflictPolicy::Ask),
+            "overwrite" => Ok(ConflictPolicy::Always(Resolution::Overwrite)),
+            "skip" => Ok(ConflictPolicy::Always(Resolution::Skip)),
+            "rename" => Ok(ConflictPolicy::Always(Resolution::Rename)),
+            "overwrite_if_newer" => Ok(ConflictPolicy::Always(Resolution::OverwriteIfNewer)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// First `name (n).ext` next to `path` that doesn't exist yet
+pub fn free_name(vfs: &dyn Vfs, path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_string());
+
+    let mut n = 1;
+    loop {
+        let name = match &extension {
+            Some(ext) => format!("{} ({}).{}", stem, n, ext),
+            None => format!("{} ({})", stem, n),
+        };
+
+        let candidate = path.with_file_name(name);
+        if vfs.symlink_stat(&candidate).is_err() {
+            return candidate;
+        }
+
+        n += 1;
+    }
+}
+
+fn is_newer(vfs: &dyn Vfs, from: &Path, to: &Path) -> bool {
+    let modified = |path: &Path| vfs.symlink_stat(path).ok().and_then(|stat| stat.modified);
+
+    match (modified(from), modified(to)) {
+        (Some(from), Some(to)) => from > to,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::vfs::MemoryFs;
+
+    fn transfer(from: &str, to: &str) -> Transfer {
+        Transfer { from: PathBuf::from(from), to: PathBuf::from(to), overwrite: false }
+    }
+
+    #[test]
+    fn free_name_counts_up_before_the_extension() {
+        let vfs = MemoryFs::from_fixture("dir/notes.txt\ndir/archive.tar.gz\ndir/Makefile").unwrap();
+        assert_eq!(free_name(&vfs, Path::new("/dir/notes.txt")), Path::new("/dir/notes (1).txt"));
+        assert_eq!(free_name(&vfs, Path::new("/dir/archive.tar.gz")), Path::new("/dir/archive.tar (1).gz"));
+        assert_eq!(free_name(&vfs, Path::new("/dir/Makefile")), Path::new("/dir/Makefile (1)"));
+
+        vfs.write(Path::new("/dir/notes (1).txt"), b"").unwrap();
+        vfs.write(Path::new("/dir/notes (2).txt"), b"").unwrap();
+        assert_eq!(free_name(&vfs, Path::new("/dir/notes.txt")), Path::new("/dir/notes (3).txt"));
+    }
+
+    #[test]
+    fn overwriting_a_path_with_itself_is_skipped() {
+        let vfs = MemoryFs::from_fixture("a").unwrap();
+
+        assert!(Resolution::Overwrite.resolve(&vfs, transfer("/a", "/a")).is_none());
+        assert!(Resolution::OverwriteIfNewer.resolve(&vfs, transfer("/a", "/a")).is_none());
+        assert_eq!(Resolution::Rename.resolve(&vfs, transfer("/a", "/a")).unwrap().to, Path::new("/a (1)"));
+    }
+
+    #[test]
+    fn only_newer_sources_overwrite_if_newer() {
+        let vfs = MemoryFs::from_fixture("old @100\nnew @200").unwrap();
+
+        let resolved = Resolution::OverwriteIfNewer.resolve(&vfs, transfer("/new", "/old")).unwrap();
+        assert!(resolved.overwrite);
+        assert!(Resolution::OverwriteIfNewer.resolve(&vfs, transfer("/old", "/new")).is_none());
+    }
+
+    #[test]
+    fn policies_parse_from_the_config() {
+        assert_eq!("ask".parse(), Ok(ConflictPolicy::Ask));
+        assert_eq!("overwrite_if_newer".parse(), Ok(ConflictPolicy::Always(Resolution::OverwriteIfNewer)));
+        assert_eq!("never".parse::<ConflictPolicy>(), Err(()));
+    }
+}
@@ -0,0 +1,108 @@
+//! New files and directories, typed as paths relative to the cursor.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::app::config::templates_dir;
+use crate::app::jobs::{copy_path, JobProgress};
+use crate::app::journal::Operation;
+use crate::app::vfs::Vfs;
+
+#[derive(Debug, Clone)]
+pub enum NewEntry {
+    File,
+    Directory,
+    /// Copy of a file or directory from the templates directory
+    Template(PathBuf),
+}
+
+/// Entries of the templates directory sorted by name
+pub fn templates() -> Vec<PathBuf> {
+    let mut templates: Vec<PathBuf> = match fs::read_dir(templates_dir()) {
+        Ok(dir) => dir.flatten().map(|entry| entry.path()).collect(),
+        Err(_) => Vec::new(),
+    };
+
+    templates.sort();
+    templates
+}
+
+/// Accept only plain relative paths so nothing is created outside the cursor
+pub fn parse_relative(text: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(text.trim());
+
+    if path.as_os_str().is_empty() {
+        return Err(String::from("Name is empty"));
+    }
+
+    if !path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("'{}' must stay inside the current directory", path.display()));
+    }
+
+    if path.file_name().is_none() {
+        return Err(format!("'{}' is not a valid name", path.display()));
+    }
+
+    Ok(path)
+}
+
+/// Create `relative` under `cursor` with its missing parents, like `mkdir -p`.
+///
+/// Everything created is pushed to `operations`, also when a later part fails.
+/// Templates are read from the local disk, so `vfs` must be local for them.
+pub fn create(
+    vfs: &dyn Vfs,
+    cursor: &Path,
+    relative: &Path,
+    entry: &NewEntry,
+    operations: &mut Vec<Operation>,
+) -> io::Result<()> {
+    let target = cursor.join(relative);
+
+    if vfs.symlink_stat(&target).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", relative.display())));
+    }
+
+    if let Some(parent) = target.parent() {
+        create_dirs(vfs, parent, operations)?;
+    }
+
+    match entry {
+        NewEntry::File => {
+            vfs.create_file(&target)?;
+            operations.push(Operation::CreateFile { path: target });
+        }
+        NewEntry::Directory => {
+            vfs.create_dir(&target)?;
+            operations.push(Operation::Mkdir { path: target });
+        }
+        NewEntry::Template(template) => {
+            let result = copy_path(vfs, template, &target, &JobProgress::default());
+
+            // even a partial copy has to be undoable
+            if vfs.symlink_stat(&target).is_ok() {
+                operations.push(Operation::Copy { from: template.clone(), to: target });
+            }
+            result?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Create every missing directory down to `dir`, outermost first
+fn create_dirs(vfs: &dyn Vfs, dir: &Path, operations: &mut Vec<Operation>) -> io::Result<()> {
+    if vfs.stat(dir).is_ok_and(|stat| stat.is_dir()) {
+        return Ok(());
+    }
+
+    if let Some(parent) = dir.parent() {
+        create_dirs(vfs, parent, operations)?;
+    }
+
+    vfs.create_dir(dir)?;
+    operations.push(Operation::Mkdir { path: dir.to_path_buf() });
+
+    Ok(())
+}
@@ -0,0 +1,392 @@
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use crate::app::batch_rename::{BatchRenameOptions, CaseConversion};
+use crate::app::create::NewEntry;
+use crate::app::jobs::{Transfer, TransferMode};
+use crate::app::name_edit::NameChange;
+use crate::app::permissions::ModeRule;
+use crate::app::trash::TrashEntry;
+use crate::app::xattrs::Xattr;
+use crate::inputs::key::Key;
+
+/// Modal popup that takes every key press until it is closed
+#[derive(Clone)]
+pub enum Dialog {
+    Conflict(ConflictDialog),
+    Confirm(ConfirmDialog),
+    Input(InputDialog),
+    BatchRename(BatchRenameDialog),
+    Chmod(ChmodDialog),
+    Message(MessageDialog),
+    Xattrs(XattrDialog),
+    PathList(PathListDialog),
+}
+
+/// Files found by a search, Enter jumps to the selected one
+#[derive(Clone)]
+pub struct PathListDialog {
+    pub title: String,
+    /// Each path with a line of detail shown after it
+    pub items: Vec<(PathBuf, String)>,
+    pub selected: usize,
+}
+
+/// Extended attributes of one entry
+#[derive(Clone)]
+pub struct XattrDialog {
+    pub path: PathBuf,
+    pub attrs: Vec<Xattr>,
+    pub selected: usize,
+    /// Show the selected value as a hex dump even if it is text
+    pub hex: bool,
+}
+
+impl XattrDialog {
+    pub fn current(&self) -> Option<&Xattr> {
+        self.attrs.get(self.selected)
+    }
+}
+
+/// Result report closed by any key
+#[derive(Clone)]
+pub struct MessageDialog {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BatchRenameField {
+    Find,
+    Replace,
+    Start,
+    Padding,
+    Case,
+    Extension,
+}
+
+impl BatchRenameField {
+    const ORDER: [BatchRenameField; 6] = [
+        BatchRenameField::Find,
+        BatchRenameField::Replace,
+        BatchRenameField::Start,
+        BatchRenameField::Padding,
+        BatchRenameField::Case,
+        BatchRenameField::Extension,
+    ];
+
+    pub fn all() -> &'static [BatchRenameField] {
+        &Self::ORDER
+    }
+
+    fn offset(&self, by: isize) -> Self {
+        let index = Self::ORDER.iter().position(|field| field == self).unwrap() as isize;
+        let len = Self::ORDER.len() as isize;
+        Self::ORDER[(index + by).rem_euclid(len) as usize]
+    }
+}
+
+/// Rename every source from one set of rules, with a live preview
+#[derive(Clone)]
+pub struct BatchRenameDialog {
+    pub sources: Vec<PathBuf>,
+    pub find: TextInput,
+    pub replace: TextInput,
+    pub start: TextInput,
+    pub padding: TextInput,
+    pub case: CaseConversion,
+    pub extension: TextInput,
+    pub focus: BatchRenameField,
+}
+
+impl BatchRenameDialog {
+    pub fn new(sources: Vec<PathBuf>) -> Self {
+        Self {
+            sources,
+            find: TextInput::new("(.*)"),
+            replace: TextInput::new("$1"),
+            start: TextInput::new("1"),
+            padding: TextInput::new("0"),
+            case: CaseConversion::Keep,
+            extension: TextInput::default(),
+            focus: BatchRenameField::Find,
+        }
+    }
+
+    pub fn options(&self) -> BatchRenameOptions<'_> {
+        BatchRenameOptions {
+            find: self.find.text(),
+            replace: self.replace.text(),
+            start: self.start.text(),
+            padding: self.padding.text(),
+            case: self.case,
+            extension: self.extension.text(),
+        }
+    }
+
+    pub fn input(&self, field: BatchRenameField) -> Option<&TextInput> {
+        match field {
+            BatchRenameField::Find => Some(&self.find),
+            BatchRenameField::Replace => Some(&self.replace),
+            BatchRenameField::Start => Some(&self.start),
+            BatchRenameField::Padding => Some(&self.padding),
+            BatchRenameField::Case => None,
+            BatchRenameField::Extension => Some(&self.extension),
+        }
+    }
+
+    /// Move focus or edit the focused field
+    pub fn handle_key(&mut self, key: Key) {
+        match key {
+            Key::Tab | Key::Down => self.focus = self.focus.offset(1),
+            Key::Up => self.focus = self.focus.offset(-1),
+            Key::Left if self.focus == BatchRenameField::Case => self.case = self.case.previous(),
+            Key::Right | Key::Char(' ') if self.focus == BatchRenameField::Case => self.case = self.case.next(),
+            _ => {
+                let input = match self.focus {
+                    BatchRenameField::Find => &mut self.find,
+                    BatchRenameField::Replace => &mut self.replace,
+                    BatchRenameField::Start => &mut self.start,
+                    BatchRenameField::Padding => &mut self.padding,
+                    BatchRenameField::Case => return,
+                    BatchRenameField::Extension => &mut self.extension,
+                };
+                input.handle_key(key);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChmodField {
+    Grid,
+    Mode,
+    DirMode,
+}
+
+/// Permission editor for the selected or marked entries
+#[derive(Clone)]
+pub struct ChmodDialog {
+    pub targets: Vec<PathBuf>,
+    /// Mode of the first target, the grid shows the rule applied to it
+    pub mode: u32,
+    pub is_dir: bool,
+    /// Class row and permission column of the grid cursor
+    pub grid_cursor: (usize, usize),
+    /// Octal or symbolic rule for files, and for directories unless `dir_rule` is set
+    pub rule: TextInput,
+    pub dir_rule: TextInput,
+    /// Also apply below the directories among the targets
+    pub recursive: bool,
+    pub focus: ChmodField,
+}
+
+impl ChmodDialog {
+    pub fn new(targets: Vec<PathBuf>, mode: u32, is_dir: bool) -> Self {
+        Self {
+            targets,
+            mode,
+            is_dir,
+            grid_cursor: (0, 0),
+            rule: TextInput::new(&format!("{:o}", mode & 0o7777)),
+            dir_rule: TextInput::default(),
+            recursive: false,
+            focus: ChmodField::Grid,
+        }
+    }
+
+    /// Rules for files and directories, the directory one falling back to the file one
+    pub fn rules(&self) -> Result<(ModeRule, ModeRule), String> {
+        let rule = ModeRule::parse(self.rule.text())?;
+        let dir_rule = if self.dir_rule.text().trim().is_empty() {
+            rule.clone()
+        }
+        else {
+            ModeRule::parse(self.dir_rule.text())?
+        };
+
+        Ok((rule, dir_rule))
+    }
+
+    /// Mode the first target would end up with
+    pub fn preview(&self) -> Result<u32, String> {
+        let (rule, dir_rule) = self.rules()?;
+        Ok(if self.is_dir { dir_rule } else { rule }.apply(self.mode, self.is_dir))
+    }
+
+    pub fn handle_key(&mut self, key: Key) {
+        match (key, self.focus) {
+            (Key::Tab, ChmodField::Grid) => self.focus = ChmodField::Mode,
+            (Key::Tab, ChmodField::Mode) => self.focus = ChmodField::DirMode,
+            (Key::Tab, ChmodField::DirMode) => self.focus = ChmodField::Grid,
+            (Key::Ctrl('r'), _) => self.recursive = !self.recursive,
+            (Key::Up, ChmodField::Grid) => self.grid_cursor.0 = (self.grid_cursor.0 + 2) % 3,
+            (Key::Down, ChmodField::Grid) => self.grid_cursor.0 = (self.grid_cursor.0 + 1) % 3,
+            (Key::Left, ChmodField::Grid) => self.grid_cursor.1 = (self.grid_cursor.1 + 2) % 3,
+            (Key::Right, ChmodField::Grid) => self.grid_cursor.1 = (self.grid_cursor.1 + 1) % 3,
+            (Key::Char(' '), ChmodField::Grid) => {
+                // the grid edits the result, so a symbolic rule turns into its octal value
+                let mode = self.preview().unwrap_or(self.mode);
+                let (row, column) = self.grid_cursor;
+                let bit = 0o400 >> (row * 3 + column);
+
+                let input = if self.is_dir && !self.dir_rule.text().trim().is_empty() { &mut self.dir_rule } else { &mut self.rule };
+                *input = TextInput::new(&format!("{:o}", mode ^ bit));
+            }
+            (_, ChmodField::Mode) => {
+                self.rule.handle_key(key);
+            }
+            (_, ChmodField::DirMode) => {
+                self.dir_rule.handle_key(key);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Single line text prompt
+#[derive(Clone)]
+pub struct InputDialog {
+    pub title: String,
+    pub input: TextInput,
+    pub purpose: InputPurpose,
+    /// Shown under the input when the last submit was rejected
+    pub error: Option<String>,
+}
+
+impl InputDialog {
+    pub fn new(title: &str, text: &str, purpose: InputPurpose) -> Self {
+        Self {
+            title: title.to_string(),
+            input: TextInput::new(text),
+            purpose,
+            error: None,
+        }
+    }
+}
+
+/// What a submitted `InputDialog` is for
+#[derive(Clone)]
+pub enum InputPurpose {
+    /// Mark or unmark the entries whose name matches
+    MarkPattern { mark: bool, regex: bool, recursive: bool },
+    /// Rename the entry to the submitted name in the same directory
+    Rename { from: PathBuf },
+    /// Create a file or directory at the submitted path, relative to the cursor
+    New(NewEntry),
+    /// Copy the chosen template to the submitted path, relative to the cursor
+    NewFromTemplate { templates: Vec<PathBuf>, index: usize },
+    /// Give the targets the submitted `user:group`
+    Chown { targets: Vec<PathBuf> },
+    /// Set an extended attribute, the text is `name=value` unless editing an existing `name`
+    SetXattr { path: PathBuf, name: Option<String> },
+    /// Edit the tags of the targets, `+tag` and `-tag` add and remove
+    Tags { targets: Vec<PathBuf> },
+    /// List the files carrying the submitted tag
+    TagView,
+    /// List the notes containing the submitted text
+    NoteSearch,
+    /// Pack the sources into the submitted archive, relative to the cursor
+    Compress { sources: Vec<PathBuf> },
+    /// Extract the archive into the submitted directory, created if missing
+    ExtractTo { archive: PathBuf },
+}
+
+/// Editable text with a cursor, counted in chars
+#[derive(Clone, Default)]
+pub struct TextInput {
+    text: String,
+    cursor: usize,
+}
+
+impl TextInput {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            cursor: text.chars().count(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.text.chars().count());
+    }
+
+    /// Apply an editing key, returning false if the key isn't an editing key
+    pub fn handle_key(&mut self, key: Key) -> bool {
+        let len = self.text.chars().count();
+
+        match key {
+            Key::Char(c) => {
+                self.text.insert(self.byte_index(self.cursor), c);
+                self.cursor += 1;
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.byte_index(self.cursor));
+            }
+            Key::Delete if self.cursor < len => {
+                self.text.remove(self.byte_index(self.cursor));
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(len),
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = len,
+            Key::Ctrl('u') => {
+                self.text.drain(..self.byte_index(self.cursor));
+                self.cursor = 0;
+            }
+            Key::Backspace | Key::Delete => {}
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.text.char_indices().nth(cursor).map_or(self.text.len(), |(index, _)| index)
+    }
+}
+
+/// Yes/no question guarding a destructive action
+#[derive(Clone)]
+pub struct ConfirmDialog {
+    pub message: String,
+    pub on_confirm: ConfirmAction,
+}
+
+#[derive(Clone)]
+pub enum ConfirmAction {
+    DeleteForever(Vec<PathBuf>),
+    PurgeTrashEntry(TrashEntry),
+    EmptyTrash,
+    RestoreAs(TrashEntry, PathBuf),
+    ApplyNameEdit(Vec<NameChange>),
+    RemoveXattr(PathBuf, OsString),
+}
+
+/// A paste waiting on the user to settle conflicting targets one at a time
+#[derive(Clone)]
+pub struct ConflictDialog {
+    pub mode: TransferMode,
+    /// Transfers ready to run once every conflict is settled
+    pub resolved: Vec<Transfer>,
+    /// Conflicting transfers, the front one is shown
+    pub pending: VecDeque<Transfer>,
+    /// Settle every remaining conflict with the next choice
+    pub apply_all: bool,
+}
+
+impl ConflictDialog {
+    pub fn current(&self) -> Option<&Transfer> {
+        self.pending.front()
+    }
+}
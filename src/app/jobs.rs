@@ -0,0 +1,513 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+use crate::app::archive;
+use crate::app::journal::Operation;
+use crate::app::trash;
+use crate::app::vfs;
+use crate::app::vfs::Vfs;
+
+/// errno returned by rename(2) when source and target are on different filesystems
+pub(crate) const EXDEV: i32 = 18;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TransferMode {
+    Copy,
+    Move,
+}
+
+/// What a job does, shown in the job list
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JobKind {
+    Copy,
+    Move,
+    Compress,
+    Extract,
+    /// Extracting an archive to browse it
+    Browse,
+}
+
+impl From<TransferMode> for JobKind {
+    fn from(mode: TransferMode) -> Self {
+        match mode {
+            TransferMode::Copy => JobKind::Copy,
+            TransferMode::Move => JobKind::Move,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Move whatever already sits at `to` to the trash before transferring
+    pub overwrite: bool,
+}
+
+/// Counters shared between a running job and the ui
+#[derive(Default)]
+pub struct JobProgress {
+    /// Copy extended attributes along with the data
+    pub preserve_xattrs: bool,
+    pub bytes_done: AtomicU64,
+    pub bytes_total: AtomicU64,
+    pub files_done: AtomicU64,
+    pub files_total: AtomicU64,
+    pub scanned: AtomicBool,
+    pub paused: AtomicBool,
+    pub cancelled: AtomicBool,
+    /// What actually happened on disk, for the undo journal
+    pub completed: Mutex<Vec<Operation>>,
+}
+
+impl JobProgress {
+    fn record(&self, operation: Operation) {
+        self.completed.lock().unwrap().push(operation);
+    }
+
+    /// Block while paused, error out once cancelled
+    pub(crate) fn checkpoint(&self) -> io::Result<()> {
+        while self.paused.load(Ordering::Relaxed) && !self.cancelled.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JobStatus {
+    Scanning,
+    Running,
+    Paused,
+    Cancelling,
+}
+
+pub struct Job {
+    pub id: usize,
+    pub kind: JobKind,
+    pub transfers: Vec<Transfer>,
+    pub progress: Arc<JobProgress>,
+    started: Instant,
+    paused_at: Option<Instant>,
+    paused_total: Duration,
+    handle: JoinHandle<io::Result<()>>,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        if self.progress.cancelled.load(Ordering::Relaxed) {
+            JobStatus::Cancelling
+        }
+        else if self.paused_at.is_some() {
+            JobStatus::Paused
+        }
+        else if !self.progress.scanned.load(Ordering::Relaxed) {
+            JobStatus::Scanning
+        }
+        else {
+            JobStatus::Running
+        }
+    }
+
+    /// Time spent actually running, pauses excluded
+    pub fn elapsed(&self) -> Duration {
+        let paused = self.paused_total + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.started.elapsed().saturating_sub(paused)
+    }
+
+    /// Bytes per second over the running time
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed().as_secs_f64();
+        if secs <= 0.0 {
+            return 0.0;
+        }
+
+        self.progress.bytes_done.load(Ordering::Relaxed) as f64 / secs
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        let throughput = self.throughput();
+        if !self.progress.scanned.load(Ordering::Relaxed) || throughput <= 0.0 {
+            return None;
+        }
+
+        let remaining = self.progress.bytes_total.load(Ordering::Relaxed)
+            .saturating_sub(self.progress.bytes_done.load(Ordering::Relaxed));
+
+        Some(Duration::from_secs_f64(remaining as f64 / throughput))
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.paused_at.take() {
+            Some(at) => {
+                self.paused_total += at.elapsed();
+                self.progress.paused.store(false, Ordering::Relaxed);
+            }
+            None => {
+                self.paused_at = Some(Instant::now());
+                self.progress.paused.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+pub struct FinishedJob {
+    pub id: usize,
+    pub kind: JobKind,
+    pub transfers: Vec<Transfer>,
+    pub operations: Vec<Operation>,
+    pub result: io::Result<()>,
+}
+
+/// Runs copy and move operations on the tokio runtime so the ui thread never blocks on disk
+pub struct JobRegistry {
+    runtime: Runtime,
+    jobs: Vec<Job>,
+    next_id: usize,
+    selected: usize,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .unwrap();
+
+        Self {
+            runtime,
+            jobs: Vec::new(),
+            next_id: 1,
+            selected: 0,
+        }
+    }
+
+    /// Run `transfers` in the background, reading and writing through `vfs`
+    pub fn spawn(&mut self, vfs: Arc<dyn Vfs>, mode: TransferMode, transfers: Vec<Transfer>, preserve_xattrs: bool) -> usize {
+        let progress = JobProgress { preserve_xattrs, ..JobProgress::default() };
+        let work = transfers.clone();
+
+        self.start(mode.into(), transfers, progress, move |progress| {
+            run_transfers(vfs.as_ref(), mode, &work, progress)
+        })
+    }
+
+    /// Pack `sources` into `archive` in the background, entries named relative to `base`
+    pub fn spawn_compress(&mut self, base: PathBuf, sources: Vec<PathBuf>, archive: PathBuf) -> usize {
+        let transfers = sources.iter()
+            .map(|source| Transfer { from: source.clone(), to: archive.clone(), overwrite: false })
+            .collect();
+
+        self.start(JobKind::Compress, transfers, JobProgress::default(), move |progress| {
+            archive::compress(&base, &sources, &archive, progress)?;
+            progress.record(Operation::Compress { base, sources, archive });
+            Ok(())
+        })
+    }
+
+    pub fn spawn_extract(&mut self, archive: PathBuf, dest: PathBuf) -> usize {
+        let transfers = vec![Transfer { from: archive.clone(), to: dest.clone(), overwrite: false }];
+
+        self.start(JobKind::Extract, transfers, JobProgress::default(), move |progress| {
+            run_extract(archive, dest, progress)
+        })
+    }
+
+    /// Extract `archive` to its browse directory `dir` in the background
+    pub fn spawn_browse(&mut self, archive: PathBuf, dir: PathBuf) -> usize {
+        let transfers = vec![Transfer { from: archive.clone(), to: dir.clone(), overwrite: false }];
+
+        self.start(JobKind::Browse, transfers, JobProgress::default(), move |progress| {
+            archive::unpack_for_browsing(&archive, &dir, progress)
+        })
+    }
+
+    fn start<F>(&mut self, kind: JobKind, transfers: Vec<Transfer>, progress: JobProgress, work: F) -> usize
+        where
+            F: FnOnce(&JobProgress) -> io::Result<()> + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let progress = Arc::new(progress);
+        let job_progress = progress.clone();
+        let handle = self.runtime.spawn_blocking(move || work(&job_progress));
+
+        info!("Job #{} started: {:?} {} item(s)", id, kind, transfers.len());
+        self.jobs.push(Job {
+            id,
+            kind,
+            transfers,
+            progress,
+            started: Instant::now(),
+            paused_at: None,
+            paused_total: Duration::ZERO,
+            handle,
+        });
+
+        id
+    }
+
+    /// Remove and return every job whose task has completed since the last poll
+    pub fn poll(&mut self) -> Vec<FinishedJob> {
+        let mut finished = Vec::new();
+        let mut index = 0;
+
+        while index < self.jobs.len() {
+            if !self.jobs[index].handle.is_finished() {
+                index += 1;
+                continue;
+            }
+
+            let job = self.jobs.remove(index);
+            let result = match self.runtime.block_on(job.handle) {
+                Ok(result) => result,
+                Err(err) => Err(io::Error::other(err.to_string())),
+            };
+
+            match &result {
+                Ok(_) => info!("Job #{} finished", job.id),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => info!("Job #{} cancelled", job.id),
+                Err(err) => error!("Job #{} failed: {}", job.id, err),
+            }
+
+            let operations = std::mem::take(&mut *job.progress.completed.lock().unwrap());
+
+            finished.push(FinishedJob {
+                id: job.id,
+                kind: job.kind,
+                transfers: job.transfers,
+                operations,
+                result,
+            });
+        }
+
+        if self.selected >= self.jobs.len() {
+            self.selected = self.jobs.len().saturating_sub(1);
+        }
+
+        finished
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        self.jobs.as_slice()
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.jobs.is_empty() {
+            self.selected = (self.selected + 1) % self.jobs.len();
+        }
+    }
+
+    pub fn toggle_pause_selected(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.selected) {
+            job.toggle_pause();
+            info!("Job #{} {}", job.id, if job.paused_at.is_some() { "paused" } else { "resumed" });
+        }
+    }
+
+    pub fn cancel_selected(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.selected) {
+            job.cancel();
+        }
+    }
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn run_transfers(vfs: &dyn Vfs, mode: TransferMode, transfers: &[Transfer], progress: &JobProgress) -> io::Result<()> {
+    for transfer in transfers {
+        scan_path(vfs, &transfer.from, progress)?;
+    }
+    progress.scanned.store(true, Ordering::Relaxed);
+
+    for transfer in transfers {
+        if transfer.overwrite && vfs.symlink_stat(&transfer.to).is_ok() {
+            let entry = trash::trash(vfs, &transfer.to)?;
+            progress.record(Operation::Trash { entry });
+        }
+
+        let from = transfer.from.clone();
+        let to = transfer.to.clone();
+
+        match mode {
+            TransferMode::Copy => {
+                let result = copy_path(vfs, &from, &to, progress);
+
+                // a partial copy is still worth undoing
+                if vfs.symlink_stat(&to).is_ok() {
+                    progress.record(Operation::Copy { from, to });
+                }
+                result?;
+            }
+            TransferMode::Move => {
+                let result = move_path(vfs, &from, &to, progress);
+
+                // the rename may have gone through before a later step failed
+                if vfs.symlink_stat(&to).is_ok() {
+                    progress.record(Operation::Move { from, to });
+                }
+                result?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract `archive` into `dest`, recording whatever appeared even if it failed half way
+fn run_extract(archive: PathBuf, dest: PathBuf, progress: &JobProgress) -> io::Result<()> {
+    let existing = fs::read_dir(&dest).ok().map(|dir| {
+        dir.flatten().map(|entry| entry.file_name()).collect::<Vec<_>>()
+    });
+
+    let result = archive::extract(&archive, &dest, progress).and_then(|extracted| {
+        if extracted.skipped > 0 {
+            warn!("{} entr(ies) already in {} were kept as they were", extracted.skipped, dest.display());
+        }
+
+        match extracted.rejected {
+            0 => Ok(()),
+            rejected => Err(io::Error::other(format!("{} entr(ies) pointing outside {} were not extracted", rejected, dest.display()))),
+        }
+    });
+
+    // undoing removes the new directory, or only the new entries of an existing one
+    let created = match existing {
+        Some(existing) => fs::read_dir(&dest)
+            .map(|dir| {
+                dir.flatten()
+                    .filter(|entry| !existing.contains(&entry.file_name()))
+                    .map(|entry| entry.path())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        None if dest.exists() => vec![dest.clone()],
+        None => Vec::new(),
+    };
+
+    if !created.is_empty() {
+        progress.record(Operation::Extract { archive, dest, created });
+    }
+
+    result
+}
+
+/// Add the size and file count of a tree to the job totals
+fn scan_path(vfs: &dyn Vfs, path: &Path, progress: &JobProgress) -> io::Result<()> {
+    progress.checkpoint()?;
+    let stat = vfs.symlink_stat(path)?;
+
+    if stat.is_dir() {
+        for entry in vfs.list(path)? {
+            scan_path(vfs, &entry, progress)?;
+        }
+    }
+    else {
+        progress.files_total.fetch_add(1, Ordering::Relaxed);
+        progress.bytes_total.fetch_add(stat.size, Ordering::Relaxed);
+    }
+
+    Ok(())
+}
+
+/// Copy a file, symlink or whole directory tree to `to`
+pub fn copy_path(vfs: &dyn Vfs, from: &Path, to: &Path, progress: &JobProgress) -> io::Result<()> {
+    progress.checkpoint()?;
+    let stat = vfs.symlink_stat(from)?;
+
+    if stat.is_symlink() {
+        vfs.symlink(&vfs.read_link(from)?, to)?;
+        progress.files_done.fetch_add(1, Ordering::Relaxed);
+    }
+    else if stat.is_dir() {
+        vfs::create_dir_all(vfs, to)?;
+
+        for entry in vfs.list(from)? {
+            copy_path(vfs, &entry, &to.join(entry.file_name().unwrap_or_default()), progress)?;
+        }
+
+        if progress.preserve_xattrs {
+            vfs.copy_xattrs(from, to)?;
+        }
+
+        // applied last so a read-only source dir doesn't block its own children
+        vfs.set_mode(to, stat.mode)?;
+    }
+    else {
+        if let Err(err) = vfs.copy_file(from, to, progress) {
+            if vfs.symlink_stat(to).is_ok_and(|stat| !stat.is_dir()) {
+                let _ = vfs.remove(to);
+            }
+            return Err(err);
+        }
+
+        if progress.preserve_xattrs {
+            vfs.copy_xattrs(from, to)?;
+        }
+
+        vfs.set_mode(to, stat.mode)?;
+        progress.files_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    Ok(())
+}
+
+/// Rename `from` to `to`, falling back to copy and delete across filesystems
+pub fn move_path(vfs: &dyn Vfs, from: &Path, to: &Path, progress: &JobProgress) -> io::Result<()> {
+    progress.checkpoint()?;
+
+    match vfs.rename(from, to) {
+        Ok(_) => {
+            // nothing is rewritten, count the whole tree as done
+            let scanned = JobProgress::default();
+            scan_path(vfs, to, &scanned)?;
+            progress.files_done.fetch_add(scanned.files_total.into_inner(), Ordering::Relaxed);
+            progress.bytes_done.fetch_add(scanned.bytes_total.into_inner(), Ordering::Relaxed);
+
+            Ok(())
+        }
+        Err(err) if err.raw_os_error() == Some(EXDEV) => {
+            // don't leave half a copy next to the untouched original
+            let existed = vfs.symlink_stat(to).is_ok();
+            if let Err(err) = copy_path(vfs, from, to, progress) {
+                if !existed && vfs.symlink_stat(to).is_ok() {
+                    if let Err(err) = vfs.remove(to) {
+                        warn!("Could not remove the partial copy {}: {}", to.display(), err);
+                    }
+                }
+                return Err(err);
+            }
+
+            vfs.remove(from)
+        }
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,566 @@
+//! Undo/redo history of file operations, persisted under the XDG state directory.
+//!
+//! Each step groups the operations of one user action so a multi-file paste
+//! or trash is undone in one go. Before a step is undone or redone every
+//! operation is checked against the filesystem, and the whole step is refused
+//! if any of them no longer applies. Steps are replayed through the `Vfs` of
+//! the browser, the one they were recorded against.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::app::archive;
+use crate::app::config::state_dir;
+use crate::app::jobs::{copy_path, move_path, JobProgress};
+use crate::app::trash;
+use crate::app::trash::{decode_path, encode_path, TrashEntry};
+use crate::app::vfs::Vfs;
+
+/// Steps kept on each stack, older ones are dropped
+const MAX_STEPS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub enum Operation {
+    Rename { from: PathBuf, to: PathBuf },
+    Move { from: PathBuf, to: PathBuf },
+    Copy { from: PathBuf, to: PathBuf },
+    Trash { entry: TrashEntry },
+    Mkdir { path: PathBuf },
+    CreateFile { path: PathBuf },
+    Symlink { target: PathBuf, path: PathBuf },
+    HardLink { target: PathBuf, path: PathBuf },
+    Chmod { path: PathBuf, old_mode: u32, new_mode: u32 },
+    /// Owners as `(uid, gid)`
+    Chown { path: PathBuf, old: (u32, u32), new: (u32, u32) },
+    /// Entries in the archive are named relative to `base`
+    Compress { base: PathBuf, sources: Vec<PathBuf>, archive: PathBuf },
+    /// `created` holds what the extraction added: `dest` itself if it was new,
+    /// otherwise the new entries inside it
+    Extract { archive: PathBuf, dest: PathBuf, created: Vec<PathBuf> },
+}
+
+impl Operation {
+    /// Make sure the operation can be reverted without clobbering anything,
+    /// `vacated` holds what the operations reverted before it will have freed
+    fn check_undo(&self, vfs: &dyn Vfs, vacated: &[PathBuf]) -> io::Result<()> {
+        match self {
+            Operation::Rename { from, to } | Operation::Move { from, to } => {
+                expect_exists(vfs, to)?;
+                expect_free(vfs, from, vacated)?;
+                expect_exists(vfs, from.parent().unwrap_or(from))
+            }
+            Operation::Copy { to, .. } => expect_exists(vfs, to),
+            Operation::Trash { entry } => {
+                expect_exists(vfs, &entry.path())?;
+                expect_free(vfs, &entry.original_path, vacated)
+            }
+            Operation::Mkdir { path } => expect_empty_dir(vfs, path, vacated),
+            Operation::CreateFile { path } | Operation::Symlink { path, .. } => expect_exists(vfs, path),
+            Operation::HardLink { path, .. } => expect_other_links(vfs, path),
+            Operation::Chmod { path, new_mode, .. } => expect_mode(vfs, path, *new_mode),
+            Operation::Chown { path, new, .. } => expect_owner(vfs, path, *new),
+            Operation::Compress { archive, .. } => expect_exists(vfs, archive),
+            Operation::Extract { created, .. } => created.iter().try_for_each(|path| expect_exists(vfs, path)),
+        }
+    }
+
+    /// Path that reverting the operation frees
+    fn vacated_by_undo(&self) -> Option<&PathBuf> {
+        match self {
+            Operation::Rename { to: path, .. }
+            | Operation::Move { to: path, .. }
+            | Operation::Copy { to: path, .. }
+            | Operation::Mkdir { path }
+            | Operation::CreateFile { path }
+            | Operation::Symlink { path, .. }
+            | Operation::HardLink { path, .. }
+            | Operation::Compress { archive: path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Path that applying the operation again frees
+    fn vacated_by_redo(&self) -> Option<&PathBuf> {
+        match self {
+            Operation::Rename { from, .. } | Operation::Move { from, .. } => Some(from),
+            Operation::Trash { entry } => Some(&entry.original_path),
+            _ => None,
+        }
+    }
+
+    /// Make sure the operation can be applied again,
+    /// `vacated` holds what the operations applied before it will have freed
+    fn check_redo(&self, vfs: &dyn Vfs, vacated: &[PathBuf]) -> io::Result<()> {
+        match self {
+            Operation::Rename { from, to } | Operation::Move { from, to } | Operation::Copy { from, to } => {
+                expect_exists(vfs, from)?;
+                expect_free(vfs, to, vacated)
+            }
+            Operation::Trash { entry } => expect_exists(vfs, &entry.original_path),
+            Operation::Mkdir { path } | Operation::CreateFile { path } | Operation::Symlink { path, .. } => {
+                expect_free(vfs, path, vacated)
+            }
+            Operation::HardLink { target, path } => {
+                expect_exists(vfs, target)?;
+                expect_free(vfs, path, vacated)
+            }
+            Operation::Chmod { path, old_mode, .. } => expect_mode(vfs, path, *old_mode),
+            Operation::Chown { path, old, .. } => expect_owner(vfs, path, *old),
+            Operation::Compress { sources, archive, .. } => {
+                expect_local(vfs)?;
+                sources.iter().try_for_each(|path| expect_exists(vfs, path))?;
+                expect_missing(vfs, archive)
+            }
+            Operation::Extract { archive, created, .. } => {
+                expect_local(vfs)?;
+                expect_exists(vfs, archive)?;
+                created.iter().try_for_each(|path| expect_missing(vfs, path))
+            }
+        }
+    }
+
+    fn undo(&mut self, vfs: &dyn Vfs) -> io::Result<()> {
+        match self {
+            Operation::Rename { from, to } => vfs.rename(to, from),
+            Operation::Move { from, to } => move_path(vfs, to, from, &JobProgress::default()),
+            Operation::Copy { to: path, .. } | Operation::CreateFile { path } => trash::trash(vfs, path).map(|_| ()),
+            Operation::Trash { entry } => trash::restore(vfs, entry),
+            // checked to be empty beforehand
+            Operation::Mkdir { path } => vfs.remove(path),
+            // a link holds no data of its own
+            Operation::Symlink { path, .. } | Operation::HardLink { path, .. } => vfs.remove(path),
+            Operation::Chmod { path, old_mode, .. } => vfs.set_mode(path, *old_mode),
+            Operation::Chown { path, old, .. } => vfs.set_owner(path, old.0, old.1),
+            Operation::Compress { archive, .. } => trash::trash(vfs, archive).map(|_| ()),
+            Operation::Extract { created, .. } => {
+                created.iter().try_for_each(|path| trash::trash(vfs, path).map(|_| ()))
+            }
+        }
+    }
+
+    fn redo(&mut self, vfs: &dyn Vfs) -> io::Result<()> {
+        match self {
+            Operation::Rename { from, to } => vfs.rename(from, to),
+            Operation::Move { from, to } => move_path(vfs, from, to, &JobProgress::default()),
+            Operation::Copy { from, to } => copy_path(vfs, from, to, &JobProgress::default()),
+            Operation::Trash { entry } => {
+                // the trash may pick another name this time
+                *entry = trash::trash(vfs, &entry.original_path)?;
+                Ok(())
+            }
+            Operation::Mkdir { path } => vfs.create_dir(path),
+            Operation::CreateFile { path } => vfs.create_file(path),
+            Operation::Symlink { target, path } => vfs.symlink(target, path),
+            Operation::HardLink { target, path } => vfs.hard_link(target, path),
+            Operation::Chmod { path, new_mode, .. } => vfs.set_mode(path, *new_mode),
+            Operation::Chown { path, new, .. } => vfs.set_owner(path, new.0, new.1),
+            // archives are only ever made on the local disk, checked by `check_redo`
+            Operation::Compress { base, sources, archive } => {
+                archive::compress(base, sources, archive, &JobProgress::default())
+            }
+            Operation::Extract { archive, dest, .. } => archive::extract(archive, dest, &JobProgress::default()).map(|_| ()),
+        }
+    }
+
+    fn encode(&self) -> String {
+        match self {
+            Operation::Rename { from, to } => format!("rename\t{}\t{}", encode_path(from), encode_path(to)),
+            Operation::Move { from, to } => format!("move\t{}\t{}", encode_path(from), encode_path(to)),
+            Operation::Copy { from, to } => format!("copy\t{}\t{}", encode_path(from), encode_path(to)),
+            Operation::Trash { entry } => format!(
+                "trash\t{}\t{}",
+                encode_path(Path::new(&entry.name)),
+                encode_path(&entry.original_path),
+            ),
+            Operation::Mkdir { path } => format!("mkdir\t{}", encode_path(path)),
+            Operation::CreateFile { path } => format!("create\t{}", encode_path(path)),
+            Operation::Symlink { target, path } => format!("symlink\t{}\t{}", encode_path(target), encode_path(path)),
+            Operation::HardLink { target, path } => format!("hardlink\t{}\t{}", encode_path(target), encode_path(path)),
+            Operation::Chmod { path, old_mode, new_mode } => {
+                format!("chmod\t{}\t{:o}\t{:o}", encode_path(path), old_mode, new_mode)
+            }
+            Operation::Chown { path, old, new } => {
+                format!("chown\t{}\t{}:{}\t{}:{}", encode_path(path), old.0, old.1, new.0, new.1)
+            }
+            Operation::Compress { base, sources, archive } => {
+                let sources: Vec<String> = sources.iter().map(|path| encode_path(path)).collect();
+                format!("compress\t{}\t{}\t{}", encode_path(archive), encode_path(base), sources.join("\t"))
+            }
+            Operation::Extract { archive, dest, created } => {
+                let created: Vec<String> = created.iter().map(|path| encode_path(path)).collect();
+                format!("extract\t{}\t{}\t{}", encode_path(archive), encode_path(dest), created.join("\t"))
+            }
+        }
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let path = |index: usize| fields.get(index).map(|field| decode_path(field));
+        let mode = |index: usize| fields.get(index).and_then(|field| u32::from_str_radix(field, 8).ok());
+        let paths_from = |index: usize| fields.iter().skip(index).map(|field| decode_path(field)).collect::<Vec<_>>();
+        let owner = |index: usize| {
+            let (uid, gid) = fields.get(index)?.split_once(':')?;
+            Some((uid.parse().ok()?, gid.parse().ok()?))
+        };
+
+        let operation = match *fields.first()? {
+            "rename" => Operation::Rename { from: path(1)?, to: path(2)? },
+            "move" => Operation::Move { from: path(1)?, to: path(2)? },
+            "copy" => Operation::Copy { from: path(1)?, to: path(2)? },
+            "trash" => Operation::Trash {
+                entry: TrashEntry {
+                    name: path(1)?.into_os_string(),
+                    original_path: path(2)?,
+                    deleted_at: None,
+                    size: 0,
+                },
+            },
+            "mkdir" => Operation::Mkdir { path: path(1)? },
+            "create" => Operation::CreateFile { path: path(1)? },
+            "symlink" => Operation::Symlink { target: path(1)?, path: path(2)? },
+            "hardlink" => Operation::HardLink { target: path(1)?, path: path(2)? },
+            "chmod" => Operation::Chmod { path: path(1)?, old_mode: mode(2)?, new_mode: mode(3)? },
+            "chown" => Operation::Chown { path: path(1)?, old: owner(2)?, new: owner(3)? },
+            "compress" => Operation::Compress { archive: path(1)?, base: path(2)?, sources: paths_from(3) },
+            "extract" => Operation::Extract { archive: path(1)?, dest: path(2)?, created: paths_from(3) },
+            _ => return None,
+        };
+
+        Some(operation)
+    }
+}
+
+/// Operations done by one user action, undone and redone together
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Default)]
+pub struct Journal {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    /// File the journal is saved to, `None` keeps it in memory only
+    path: Option<PathBuf>,
+}
+
+impl Journal {
+    /// Load the journal left by a previous run, starting empty if there is none
+    pub fn load() -> Self {
+        let path = state_dir().join("journal");
+        let content = fs::read_to_string(&path);
+        let mut journal = Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            path: Some(path),
+        };
+
+        let content = match content {
+            Ok(content) => content,
+            Err(_) => return journal,
+        };
+
+        let mut stack = &mut journal.undo;
+        for line in content.lines() {
+            match line {
+                "[undo]" => stack = &mut journal.undo,
+                "[redo]" => stack = &mut journal.redo,
+                "step" => stack.push(Step { operations: Vec::new() }),
+                _ => match (stack.last_mut(), Operation::decode(line)) {
+                    (Some(step), Some(operation)) => step.operations.push(operation),
+                    _ => warn!("Journal: ignoring malformed line '{}'", line),
+                },
+            }
+        }
+
+        journal
+    }
+
+    /// Record a finished user action, this forgets everything that could be redone
+    pub fn record(&mut self, operations: Vec<Operation>) {
+        if operations.is_empty() {
+            return;
+        }
+
+        self.undo.push(Step { operations });
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+
+        self.save();
+    }
+
+    /// Revert the latest step, returning how many operations were reverted
+    pub fn undo(&mut self, vfs: &dyn Vfs) -> io::Result<usize> {
+        let mut step = match self.undo.pop() {
+            Some(step) => step,
+            None => return Ok(0),
+        };
+
+        // each operation is checked against what the ones reverted before it leave behind,
+        // like a paste that overwrote: its target is freed before the old entry comes back
+        let checked = (0..step.operations.len()).rev().try_for_each(|index| {
+            let vacated: Vec<PathBuf> = step.operations[index + 1..].iter()
+                .filter_map(Operation::vacated_by_undo)
+                .cloned()
+                .collect();
+            step.operations[index].check_undo(vfs, &vacated)
+        });
+
+        if let Err(err) = checked {
+            self.undo.push(step);
+            return Err(err);
+        }
+
+        let result = step.operations.iter_mut().rev().try_for_each(|operation| operation.undo(vfs));
+        let count = step.operations.len();
+
+        // a half reverted step can't be trusted in either direction
+        if result.is_ok() {
+            self.redo.push(step);
+        }
+        self.save();
+
+        result.map(|_| count)
+    }
+
+    /// Apply the latest undone step again, returning how many operations were applied
+    pub fn redo(&mut self, vfs: &dyn Vfs) -> io::Result<usize> {
+        let mut step = match self.redo.pop() {
+            Some(step) => step,
+            None => return Ok(0),
+        };
+
+        let checked = (0..step.operations.len()).try_for_each(|index| {
+            let vacated: Vec<PathBuf> = step.operations[..index].iter()
+                .filter_map(Operation::vacated_by_redo)
+                .cloned()
+                .collect();
+            step.operations[index].check_redo(vfs, &vacated)
+        });
+
+        if let Err(err) = checked {
+            self.redo.push(step);
+            return Err(err);
+        }
+
+        let result = step.operations.iter_mut().try_for_each(|operation| operation.redo(vfs));
+        let count = step.operations.len();
+
+        if result.is_ok() {
+            self.undo.push(step);
+        }
+        self.save();
+
+        result.map(|_| count)
+    }
+
+    fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut content = String::new();
+
+        for (header, stack) in [("[undo]", &self.undo), ("[redo]", &self.redo)] {
+            content.push_str(header);
+            content.push('\n');
+
+            for step in stack {
+                content.push_str("step\n");
+                for operation in &step.operations {
+                    content.push_str(&operation.encode());
+                    content.push('\n');
+                }
+            }
+        }
+
+        let result = path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, content));
+
+        if let Err(err) = result {
+            warn!("Cannot save journal to {}: {}", path.display(), err);
+        }
+    }
+}
+
+fn expect_exists(vfs: &dyn Vfs, path: &Path) -> io::Result<()> {
+    match vfs.symlink_stat(path) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(changed(path, "no longer exists")),
+    }
+}
+
+fn expect_missing(vfs: &dyn Vfs, path: &Path) -> io::Result<()> {
+    match vfs.symlink_stat(path) {
+        Ok(_) => Err(changed(path, "is in the way")),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Missing, or about to be freed by another operation of the step
+fn expect_free(vfs: &dyn Vfs, path: &Path, vacated: &[PathBuf]) -> io::Result<()> {
+    if vacated.iter().any(|vacated| vacated == path) {
+        Ok(())
+    }
+    else {
+        expect_missing(vfs, path)
+    }
+}
+
+fn expect_empty_dir(vfs: &dyn Vfs, path: &Path, vacated: &[PathBuf]) -> io::Result<()> {
+    let entries = vfs.symlink_stat(path).and_then(|_| vfs.list(path));
+    match entries.map(|entries| entries.iter().all(|entry| vacated.contains(entry))) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(changed(path, "is no longer empty")),
+        Err(_) => Err(changed(path, "no longer exists")),
+    }
+}
+
+/// Removing a hard link is only harmless while another name keeps the data
+fn expect_other_links(vfs: &dyn Vfs, path: &Path) -> io::Result<()> {
+    match vfs.symlink_stat(path) {
+        Ok(stat) if stat.nlink > 1 => Ok(()),
+        Ok(_) => Err(changed(path, "is the last link to its data")),
+        Err(_) => Err(changed(path, "no longer exists")),
+    }
+}
+
+fn expect_mode(vfs: &dyn Vfs, path: &Path, mode: u32) -> io::Result<()> {
+    match vfs.symlink_stat(path) {
+        Ok(stat) if stat.mode == mode => Ok(()),
+        Ok(_) => Err(changed(path, "had its mode changed")),
+        Err(_) => Err(changed(path, "no longer exists")),
+    }
+}
+
+fn expect_owner(vfs: &dyn Vfs, path: &Path, (uid, gid): (u32, u32)) -> io::Result<()> {
+    match vfs.symlink_stat(path) {
+        Ok(stat) if stat.uid == uid && stat.gid == gid => Ok(()),
+        Ok(_) => Err(changed(path, "had its owner changed")),
+        Err(_) => Err(changed(path, "no longer exists")),
+    }
+}
+
+/// Archives are read and written with `std::fs`
+fn expect_local(vfs: &dyn Vfs) -> io::Result<()> {
+    if vfs.is_local() {
+        Ok(())
+    }
+    else {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "archives can only be made on the local disk"))
+    }
+}
+
+fn changed(path: &Path, what: &str) -> io::Error {
+    io::Error::other(format!("{} {}", path.display(), what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::vfs::MemoryFs;
+
+    fn read(vfs: &dyn Vfs, path: &str) -> String {
+        String::from_utf8(vfs.read(Path::new(path)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rename_is_undone_and_redone() {
+        let vfs = MemoryFs::from_fixture("dir/a = text").unwrap();
+        let mut journal = Journal::default();
+
+        vfs.rename(Path::new("/dir/a"), Path::new("/dir/b")).unwrap();
+        journal.record(vec![Operation::Rename { from: PathBuf::from("/dir/a"), to: PathBuf::from("/dir/b") }]);
+
+        assert_eq!(journal.undo(&vfs).unwrap(), 1);
+        assert_eq!(read(&vfs, "/dir/a"), "text");
+        assert_eq!(journal.undo(&vfs).unwrap(), 0);
+
+        assert_eq!(journal.redo(&vfs).unwrap(), 1);
+        assert_eq!(read(&vfs, "/dir/b"), "text");
+        assert!(vfs.symlink_stat(Path::new("/dir/a")).is_err());
+    }
+
+    #[test]
+    fn nothing_is_clobbered_and_the_step_is_kept() {
+        let vfs = MemoryFs::from_fixture("dir/b = text").unwrap();
+        let mut journal = Journal::default();
+        journal.record(vec![Operation::Rename { from: PathBuf::from("/dir/a"), to: PathBuf::from("/dir/b") }]);
+
+        vfs.write(Path::new("/dir/a"), b"new").unwrap();
+        let err = journal.undo(&vfs).unwrap_err();
+        assert_eq!(err.to_string(), "/dir/a is in the way");
+
+        vfs.remove(Path::new("/dir/a")).unwrap();
+        assert_eq!(journal.undo(&vfs).unwrap(), 1);
+        assert_eq!(read(&vfs, "/dir/a"), "text");
+    }
+
+    #[test]
+    fn overwrite_is_undone_and_redone() {
+        let vfs = MemoryFs::from_fixture("src/a = new\ndir/a = old").unwrap();
+        let mut journal = Journal::default();
+
+        // what a paste that overwrote records: the old entry trashed, then the copy
+        let entry = trash::trash(&vfs, Path::new("/dir/a")).unwrap();
+        copy_path(&vfs, Path::new("/src/a"), Path::new("/dir/a"), &JobProgress::default()).unwrap();
+        journal.record(vec![
+            Operation::Trash { entry },
+            Operation::Copy { from: PathBuf::from("/src/a"), to: PathBuf::from("/dir/a") },
+        ]);
+
+        assert_eq!(journal.undo(&vfs).unwrap(), 2);
+        assert_eq!(read(&vfs, "/dir/a"), "old");
+
+        assert_eq!(journal.redo(&vfs).unwrap(), 2);
+        assert_eq!(read(&vfs, "/dir/a"), "new");
+
+        assert_eq!(journal.undo(&vfs).unwrap(), 2);
+        assert_eq!(read(&vfs, "/dir/a"), "old");
+    }
+
+    #[test]
+    fn new_directories_are_undone_with_what_was_made_in_them() {
+        let vfs = MemoryFs::from_fixture("dir/").unwrap();
+        let mut journal = Journal::default();
+
+        vfs.create_dir(Path::new("/dir/new")).unwrap();
+        vfs.create_file(Path::new("/dir/new/file")).unwrap();
+        journal.record(vec![
+            Operation::Mkdir { path: PathBuf::from("/dir/new") },
+            Operation::CreateFile { path: PathBuf::from("/dir/new/file") },
+        ]);
+
+        assert_eq!(journal.undo(&vfs).unwrap(), 2);
+        assert!(vfs.symlink_stat(Path::new("/dir/new")).is_err());
+
+        assert_eq!(journal.redo(&vfs).unwrap(), 2);
+        assert!(vfs.symlink_stat(Path::new("/dir/new/file")).is_ok());
+    }
+
+    #[test]
+    fn operations_survive_encoding() {
+        let operations = [
+            Operation::Move { from: PathBuf::from("/a\tb"), to: PathBuf::from("/c\nd") },
+            Operation::Chmod { path: PathBuf::from("/x"), old_mode: 0o644, new_mode: 0o4755 },
+            Operation::Chown { path: PathBuf::from("/x"), old: (0, 0), new: (1000, 100) },
+            Operation::Compress { base: PathBuf::from("/"), sources: vec![PathBuf::from("/a"), PathBuf::from("/b")], archive: PathBuf::from("/c.zip") },
+        ];
+
+        for operation in operations {
+            let line = operation.encode();
+            assert!(!line.contains('\n'));
+            assert_eq!(Operation::decode(&line).unwrap().encode(), line);
+        }
+
+        assert!(Operation::decode("rename\t/a").is_none());
+        assert!(Operation::decode("chmod\t/a\t9\t7").is_none());
+    }
+}
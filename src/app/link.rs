@@ -0,0 +1,107 @@
+//! Symbolic and hard links to marked entries, created in the cursor directory.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::app::journal::Operation;
+use crate::app::vfs::Vfs;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LinkKind {
+    /// Symlink storing the full path of the target
+    Absolute,
+    /// Symlink storing the path of the target as seen from the link's directory
+    Relative,
+    Hard,
+}
+
+/// Link `target` into `dir` under the same name
+pub fn link(vfs: &dyn Vfs, target: &Path, dir: &Path, kind: LinkKind) -> io::Result<Operation> {
+    let name = target.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no name", target.display())))?;
+    let path = dir.join(name);
+
+    if vfs.symlink_stat(&path).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path.display())));
+    }
+
+    match kind {
+        LinkKind::Absolute | LinkKind::Relative => {
+            let target = if kind == LinkKind::Relative { relative_path(dir, target) } else { target.to_path_buf() };
+            vfs.symlink(&target, &path)?;
+
+            Ok(Operation::Symlink { target, path })
+        }
+        LinkKind::Hard => {
+            let stat = vfs.symlink_stat(target)?;
+            if stat.is_dir() {
+                return Err(io::Error::other("directories cannot be hard linked"));
+            }
+
+            if stat.dev != vfs.stat(dir)?.dev {
+                return Err(io::Error::other("not on the same filesystem"));
+            }
+
+            vfs.hard_link(target, &path)?;
+
+            Ok(Operation::HardLink { target: target.to_path_buf(), path })
+        }
+    }
+}
+
+/// Follow `path` through every symlink to the real entry, refusing link cycles
+pub fn resolve(vfs: &dyn Vfs, path: &Path) -> io::Result<PathBuf> {
+    let mut current = real_parent(vfs, path)?;
+    let mut seen = HashSet::new();
+
+    loop {
+        let stat = vfs.symlink_stat(&current).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => io::Error::new(err.kind(), format!("broken link, {} does not exist", current.display())),
+            _ => err,
+        })?;
+
+        if !stat.is_symlink() {
+            return Ok(current);
+        }
+
+        if !seen.insert(current.clone()) {
+            return Err(io::Error::other(format!("{} is part of a link cycle", current.display())));
+        }
+
+        // a relative target is relative to the link's own directory
+        let target = vfs.read_link(&current)?;
+        current = real_parent(vfs, &current.parent().unwrap_or(Path::new("/")).join(target))?;
+    }
+}
+
+/// `path` with its directory canonicalized but its last component left as is,
+/// so revisiting a link always gives the same path
+fn real_parent(vfs: &dyn Vfs, path: &Path) -> io::Result<PathBuf> {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => Ok(vfs.canonicalize(parent)?.join(name)),
+        _ => vfs.canonicalize(path),
+    }
+}
+
+/// Path leading from the directory `from` to `to`, both absolute
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+
+    relative
+}
@@ -1,19 +1,67 @@
-use log::{debug};
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+
+use log::{debug, error, info, warn};
 use actions::Action;
+use crate::app::archive::{ArchiveFormat, PendingBrowse};
+use crate::app::config::Config;
+use crate::app::config::templates_dir;
+use crate::app::conflict::{free_name, ConflictPolicy, Resolution};
+use crate::app::create::NewEntry;
+use crate::app::dialog::{
+    BatchRenameDialog, ChmodDialog, ConfirmAction, ConfirmDialog, ConflictDialog, Dialog, InputDialog, InputPurpose,
+    MessageDialog, PathListDialog, TextInput, XattrDialog,
+};
+use crate::app::jobs::{JobRegistry, Transfer, TransferMode};
+use crate::app::journal::{Journal, Operation};
+use crate::app::link::LinkKind;
+use crate::app::name_edit::{NameChange, NameEdit};
+use crate::app::notes::{NoteEdit, NoteStore};
+use crate::app::owners::Owners;
+use crate::app::pattern::Pattern;
 use crate::app::state::AppState::Initialized;
 use crate::app::state::File;
+use crate::app::tags::{TagSearch, TagStore};
+use crate::app::vfs::LocalFs;
+use crate::inputs::key::Key;
 
 use self::actions::Actions;
 use self::state::AppState;
 
 pub mod actions;
+pub mod archive;
+pub mod batch_rename;
+pub mod config;
+pub mod conflict;
+pub mod create;
+pub mod dialog;
+pub mod jobs;
+pub mod journal;
+pub mod link;
+pub mod name_edit;
+pub mod notes;
+pub mod owners;
+pub mod pattern;
+pub mod permissions;
 pub mod state;
+pub mod tags;
+pub mod trash;
 pub mod ui;
+pub mod vfs;
+pub mod xattrs;
+
+/// Help lines skipped by Page Up and Page Down
+const HELP_PAGE: u16 = 10;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum AppReturn {
     Exit,
     Continue,
+    /// Suspend the ui and run `$EDITOR` on the file, then call `App::editor_closed`
+    RunEditor(PathBuf),
 }
 
 /// The main application, containing the state
@@ -21,11 +69,58 @@ pub struct App {
     actions: Actions,
     /// State
     state: AppState,
+    /// Background copy and move jobs
+    jobs: JobRegistry,
+    config: Config,
+    /// Undo/redo history of file operations
+    journal: Journal,
+    /// Names handed to `$EDITOR`, applied once it exits
+    name_edit: Option<NameEdit>,
+    /// User and group names by id
+    owners: Owners,
+    /// Tags of entries on filesystems without xattr support
+    tags: TagStore,
+    /// Tag view search still walking the tag roots
+    tag_search: Option<TagSearch>,
+    notes: NoteStore,
+    /// Note handed to `$EDITOR`, saved once it exits
+    note_edit: Option<NoteEdit>,
+    /// Archive still being extracted to be browsed
+    pending_browse: Option<PendingBrowse>,
 }
 
 impl App {
     pub fn new() -> Self {
-        let actions = vec![
+        Self {
+            config: Config::load(),
+            journal: Journal::load(),
+            owners: Owners::load(),
+            tags: TagStore::load(),
+            notes: NoteStore::load(),
+            ..Self::with_state(AppState::initialized())
+        }
+    }
+
+    /// App around `state` with default settings, nothing is loaded from or saved to disk
+    pub fn with_state(state: AppState) -> Self {
+        Self {
+            actions: Self::browser_actions(),
+            state,
+            jobs: JobRegistry::new(),
+            config: Config::default(),
+            journal: Journal::default(),
+            name_edit: None,
+            owners: Owners::default(),
+            tags: TagStore::default(),
+            tag_search: None,
+            notes: NoteStore::default(),
+            note_edit: None,
+            pending_browse: None,
+        }
+    }
+
+    fn browser_actions() -> Actions {
+        vec![
             Action::Quit,
             Action::Increment,
             Action::Decrement,
@@ -34,18 +129,95 @@ impl App {
             Action::Forward,
             Action::ToggleLog,
             Action::ToggleHelp,
-        ].into();
-        let state = AppState::initialized();
-        Self { actions, state }
+            Action::Yank,
+            Action::Cut,
+            Action::Paste,
+            Action::ToggleJobs,
+            Action::NextJob,
+            Action::PauseJob,
+            Action::CancelJob,
+            Action::Trash,
+            Action::DeleteForever,
+            Action::ToggleTrash,
+            Action::Undo,
+            Action::Redo,
+            Action::ToggleMark,
+            Action::MarkRange,
+            Action::MarkAll,
+            Action::UnmarkAll,
+            Action::InvertMarks,
+            Action::MarkPattern,
+            Action::UnmarkPattern,
+            Action::Rename,
+            Action::BatchRename,
+            Action::EditNames,
+            Action::NewFile,
+            Action::NewDirectory,
+            Action::NewFromTemplate,
+            Action::SymlinkRelative,
+            Action::SymlinkAbsolute,
+            Action::HardLink,
+            Action::JumpToLinkTarget,
+            Action::Chmod,
+            Action::Chown,
+            Action::ToggleLongListing,
+            Action::ToggleProperties,
+            Action::Xattrs,
+            Action::EditTags,
+            Action::TagView,
+            Action::EditNote,
+            Action::SearchNotes,
+            Action::Compress,
+            Action::Extract,
+            Action::ExtractTo,
+        ].into()
+    }
+
+    fn trash_actions() -> Actions {
+        vec![
+            Action::Quit,
+            Action::Increment,
+            Action::Decrement,
+            Action::ToggleLog,
+            Action::ToggleHelp,
+            Action::ToggleJobs,
+            Action::ToggleTrash,
+            Action::Restore,
+            Action::PurgeTrashEntry,
+            Action::EmptyTrash,
+        ].into()
     }
 
     pub fn do_action(&mut self, key: crate::inputs::key::Key) -> AppReturn {
+        if self.state.dialog().is_some() {
+            self.do_dialog_action(key);
+            return AppReturn::Continue;
+        }
+
+        if self.state.display_help() == Some(&true) && self.scroll_help(key) {
+            return AppReturn::Continue;
+        }
+
         if let Some(action) = self.actions.find(key) {
             debug!("Run action [{:?}]", action);
+
+            if action.modifies_entries() && self.state.in_archive() {
+                warn!("Archive contents are read-only, copy them out first");
+                return AppReturn::Continue;
+            }
+
+            if action.needs_local_fs() && !self.state.is_local() {
+                warn!("{} only works on the local disk", action);
+                return AppReturn::Continue;
+            }
+
             match action {
                 Action::Quit => AppReturn::Exit,
                 Action::Decrement => {
                     match &mut self.state {
+                        Initialized { trash_view: Some(trash_list), .. } => {
+                            trash_list.previous();
+                        }
                         Initialized { current_list, .. } => {
                             current_list.previous();
                         }
@@ -55,6 +227,9 @@ impl App {
                 },
                 Action::Increment => {
                     match &mut self.state {
+                        Initialized { trash_view: Some(trash_list), .. } => {
+                            trash_list.next();
+                        }
                         Initialized { current_list, .. } => {
                             current_list.next();
                         }
@@ -79,7 +254,14 @@ impl App {
                     AppReturn::Continue
                 },
                 Action::Forward => {
-                    self.state.read_dir();
+                    match self.state.selected() {
+                        // archives are extracted with `std::fs`, elsewhere they are plain files
+                        Some(item) if !item.is_dir && self.state.is_local() && ArchiveFormat::from_path(&item.path).is_some() => {
+                            let archive = item.path.clone();
+                            self.browse_archive(archive);
+                        }
+                        _ => self.state.read_dir(),
+                    }
 
                     AppReturn::Continue
                 }
@@ -91,6 +273,356 @@ impl App {
                 Action::ToggleHelp => {
                     self.state.toggle_help();
 
+                    AppReturn::Continue
+                }
+                Action::Yank => {
+                    self.state.set_clipboard(TransferMode::Copy);
+
+                    AppReturn::Continue
+                }
+                Action::Cut => {
+                    self.state.set_clipboard(TransferMode::Move);
+
+                    AppReturn::Continue
+                }
+                Action::Paste => {
+                    self.paste();
+
+                    AppReturn::Continue
+                }
+                Action::ToggleJobs => {
+                    self.state.toggle_jobs();
+
+                    AppReturn::Continue
+                }
+                Action::NextJob => {
+                    self.jobs.select_next();
+
+                    AppReturn::Continue
+                }
+                Action::PauseJob => {
+                    self.jobs.toggle_pause_selected();
+
+                    AppReturn::Continue
+                }
+                Action::CancelJob => {
+                    self.jobs.cancel_selected();
+
+                    AppReturn::Continue
+                }
+                Action::Trash => {
+                    self.trash_selected();
+
+                    AppReturn::Continue
+                }
+                Action::DeleteForever => {
+                    let paths = self.state.targets();
+                    let message = match paths.as_slice() {
+                        [] => None,
+                        [path] => Some(format!("Permanently delete {}?", path.file_name().unwrap_or_default().to_string_lossy())),
+                        _ => Some(format!("Permanently delete {} marked item(s)?", paths.len())),
+                    };
+
+                    if let Some(message) = message {
+                        self.state.open_dialog(Dialog::Confirm(ConfirmDialog {
+                            message,
+                            on_confirm: ConfirmAction::DeleteForever(paths),
+                        }));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::ToggleMark => {
+                    self.state.toggle_mark();
+
+                    AppReturn::Continue
+                }
+                Action::MarkRange => {
+                    self.state.mark_range();
+
+                    AppReturn::Continue
+                }
+                Action::MarkAll => {
+                    self.state.mark_all();
+
+                    AppReturn::Continue
+                }
+                Action::UnmarkAll => {
+                    self.state.unmark_all();
+
+                    AppReturn::Continue
+                }
+                Action::InvertMarks => {
+                    self.state.invert_marks();
+
+                    AppReturn::Continue
+                }
+                Action::Rename => {
+                    if let Some(selected) = self.state.selected() {
+                        let name = selected.name.to_string_lossy().to_string();
+                        let purpose = InputPurpose::Rename { from: selected.path.clone() };
+                        let mut dialog = InputDialog::new("Rename", &name, purpose);
+
+                        // start editing in front of the extension
+                        if !selected.is_dir {
+                            if let Some(stem) = selected.path.file_stem() {
+                                dialog.input.set_cursor(stem.to_string_lossy().chars().count());
+                            }
+                        }
+
+                        self.state.open_dialog(Dialog::Input(dialog));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::BatchRename => {
+                    let sources = self.state.targets();
+                    if !sources.is_empty() {
+                        self.state.open_dialog(Dialog::BatchRename(BatchRenameDialog::new(sources)));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::EditNames => self.edit_names(),
+                Action::SymlinkRelative => {
+                    self.link_marked(LinkKind::Relative);
+
+                    AppReturn::Continue
+                }
+                Action::SymlinkAbsolute => {
+                    self.link_marked(LinkKind::Absolute);
+
+                    AppReturn::Continue
+                }
+                Action::HardLink => {
+                    self.link_marked(LinkKind::Hard);
+
+                    AppReturn::Continue
+                }
+                Action::JumpToLinkTarget => {
+                    self.jump_to_link_target();
+
+                    AppReturn::Continue
+                }
+                Action::Chown => {
+                    let targets = self.state.targets();
+                    let stat = self.state.vfs().zip(targets.first()).and_then(|(vfs, path)| vfs.symlink_stat(path).ok());
+                    if let Some(stat) = stat {
+                        let owner = format!("{}:{}", self.owners.user(stat.uid), self.owners.group(stat.gid));
+                        let purpose = InputPurpose::Chown { targets };
+                        self.state.open_dialog(Dialog::Input(InputDialog::new("Change owner", &owner, purpose)));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::ToggleLongListing => {
+                    self.state.toggle_long_listing();
+
+                    AppReturn::Continue
+                }
+                Action::ToggleProperties => {
+                    self.state.toggle_properties();
+
+                    AppReturn::Continue
+                }
+                Action::Xattrs => {
+                    if let Some(path) = self.state.selected().map(|item| item.path.clone()) {
+                        self.open_xattrs(&path, 0);
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::EditTags => {
+                    let targets = self.state.targets();
+                    if let Some(path) = targets.first() {
+                        let text = self.tags.tags(path).join(", ");
+                        let purpose = InputPurpose::Tags { targets };
+                        self.state.open_dialog(Dialog::Input(InputDialog::new("Tags", &text, purpose)));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::TagView => {
+                    self.state.open_dialog(Dialog::Input(InputDialog::new("Browse by tag", "", InputPurpose::TagView)));
+
+                    AppReturn::Continue
+                }
+                Action::EditNote => {
+                    match self.state.selected().map(|item| (item.path.clone(), item.metadata.clone())) {
+                        Some((path, metadata)) => {
+                            let text = self.notes.note(&path, &metadata).unwrap_or_default().to_string();
+                            match NoteEdit::create(&path, &text) {
+                                Ok(edit) => {
+                                    let file = edit.file.clone();
+                                    self.note_edit = Some(edit);
+                                    AppReturn::RunEditor(file)
+                                }
+                                Err(err) => {
+                                    error!("Cannot write note for the editor: {}", err);
+                                    AppReturn::Continue
+                                }
+                            }
+                        }
+                        None => AppReturn::Continue,
+                    }
+                }
+                Action::Compress => {
+                    let sources = self.state.targets();
+                    let name = match sources.as_slice() {
+                        [source] => source.file_name().map(|name| name.to_string_lossy().to_string()),
+                        _ => self.state.cursor().and_then(|cursor| cursor.file_name()).map(|name| name.to_string_lossy().to_string()),
+                    };
+
+                    if !sources.is_empty() {
+                        let text = format!("{}.tar.gz", name.unwrap_or_else(|| String::from("archive")));
+                        let purpose = InputPurpose::Compress { sources };
+                        self.state.open_dialog(Dialog::Input(InputDialog::new("Compress to", &text, purpose)));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::Extract | Action::ExtractTo => {
+                    let archive = match self.state.selected() {
+                        Some(item) if ArchiveFormat::from_path(&item.path).is_some() => item.path.clone(),
+                        Some(item) => {
+                            warn!("{} is not a zip, tar, tar.gz or tar.zst archive", item.name.to_string_lossy());
+                            return AppReturn::Continue;
+                        }
+                        None => return AppReturn::Continue,
+                    };
+
+                    let mut dest = match archive::default_destination(&archive) {
+                        Some(dest) => dest,
+                        None => {
+                            error!("Cannot name a directory to extract {} to", archive.display());
+                            return AppReturn::Continue;
+                        }
+                    };
+                    if dest.symlink_metadata().is_ok() {
+                        dest = free_name(&LocalFs, &dest);
+                    }
+
+                    if *action == Action::Extract {
+                        self.jobs.spawn_extract(archive, dest);
+                    }
+                    else {
+                        let text = dest.to_string_lossy().to_string();
+                        let purpose = InputPurpose::ExtractTo { archive };
+                        self.state.open_dialog(Dialog::Input(InputDialog::new("Extract to", &text, purpose)));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::SearchNotes => {
+                    self.state.open_dialog(Dialog::Input(InputDialog::new("Search notes", "", InputPurpose::NoteSearch)));
+
+                    AppReturn::Continue
+                }
+                Action::Chmod => {
+                    let targets = self.state.targets();
+                    let stat = self.state.vfs().zip(targets.first()).and_then(|(vfs, path)| vfs.symlink_stat(path).ok());
+                    if let Some(stat) = stat {
+                        self.state.open_dialog(Dialog::Chmod(ChmodDialog::new(targets, stat.mode, stat.is_dir())));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::NewFile | Action::NewDirectory => {
+                    let (title, entry) = if *action == Action::NewFile {
+                        ("New file", NewEntry::File)
+                    }
+                    else {
+                        ("New directory", NewEntry::Directory)
+                    };
+                    self.state.open_dialog(Dialog::Input(InputDialog::new(title, "", InputPurpose::New(entry))));
+
+                    AppReturn::Continue
+                }
+                Action::NewFromTemplate => {
+                    let templates = create::templates();
+                    match templates.first() {
+                        Some(first) => {
+                            let name = first.file_name().unwrap_or_default().to_string_lossy().to_string();
+                            let purpose = InputPurpose::NewFromTemplate { templates, index: 0 };
+                            self.state.open_dialog(Dialog::Input(InputDialog::new("New from template", &name, purpose)));
+                        }
+                        None => warn!("No templates in {}", templates_dir().display()),
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::MarkPattern | Action::UnmarkPattern => {
+                    let mark = *action == Action::MarkPattern;
+                    let purpose = InputPurpose::MarkPattern { mark, regex: false, recursive: false };
+                    let title = if mark { "Mark matching" } else { "Unmark matching" };
+                    self.state.open_dialog(Dialog::Input(InputDialog::new(title, "", purpose)));
+
+                    AppReturn::Continue
+                }
+                Action::ToggleTrash => {
+                    if self.state.trash_view().is_some() {
+                        self.state.close_trash_view();
+                        self.state.refresh();
+                        self.actions = Self::browser_actions();
+                    }
+                    else {
+                        match self.state.open_trash_view() {
+                            Ok(_) => self.actions = Self::trash_actions(),
+                            Err(err) => error!("Cannot read trash: {}", err),
+                        }
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::Restore => {
+                    self.restore_selected();
+
+                    AppReturn::Continue
+                }
+                Action::PurgeTrashEntry => {
+                    if let Some(entry) = self.state.selected_trash_entry() {
+                        let message = format!("Permanently delete {} from trash?", entry.original_path.display());
+                        self.state.open_dialog(Dialog::Confirm(ConfirmDialog {
+                            message,
+                            on_confirm: ConfirmAction::PurgeTrashEntry(entry.clone()),
+                        }));
+                    }
+
+                    AppReturn::Continue
+                }
+                Action::Undo => {
+                    if let Some(vfs) = self.state.shared_vfs() {
+                        match self.journal.undo(vfs.as_ref()) {
+                            Ok(0) => info!("Nothing to undo"),
+                            Ok(count) => info!("Undid {} operation(s)", count),
+                            Err(err) => error!("Cannot undo: {}", err),
+                        }
+                    }
+                    self.state.refresh();
+
+                    AppReturn::Continue
+                }
+                Action::Redo => {
+                    if let Some(vfs) = self.state.shared_vfs() {
+                        match self.journal.redo(vfs.as_ref()) {
+                            Ok(0) => info!("Nothing to redo"),
+                            Ok(count) => info!("Redid {} operation(s)", count),
+                            Err(err) => error!("Cannot redo: {}", err),
+                        }
+                    }
+                    self.state.refresh();
+
+                    AppReturn::Continue
+                }
+                Action::EmptyTrash => {
+                    if let Some(list) = self.state.trash_view() {
+                        let message = format!("Permanently delete all {} item(s) in trash?", list.items.len());
+                        self.state.open_dialog(Dialog::Confirm(ConfirmDialog {
+                            message,
+                            on_confirm: ConfirmAction::EmptyTrash,
+                        }));
+                    }
+
                     AppReturn::Continue
                 }
             }
@@ -101,10 +633,875 @@ impl App {
     }
 
     pub fn update_on_tick(&mut self) -> AppReturn {
+        let finished = self.jobs.poll();
+        if !finished.is_empty() {
+            for job in finished {
+                if self.pending_browse.as_ref().is_some_and(|pending| pending.job == job.id) {
+                    let pending = self.pending_browse.take().unwrap();
+                    // only follow it in when the user is still where the archive was opened
+                    if job.result.is_ok() && self.state.cursor().map(PathBuf::as_path) == pending.archive.parent() {
+                        self.state.enter_archive(&pending.archive, pending.root);
+                    }
+                }
+
+                self.journal.record(job.operations);
+            }
+
+            self.state.refresh();
+        }
+
+        // entries only move under their notes when the list is read again
+        if self.state.take_relisted() {
+            if let Some(list) = self.state.current_list() {
+                self.notes.follow_renames(&list.items);
+            }
+        }
+
+        if self.tag_search.as_ref().is_some_and(TagSearch::is_finished) {
+            let search = self.tag_search.take().unwrap();
+            let tag = search.tag.clone();
+
+            let mut paths = search.join();
+            paths.extend(self.tags.sidecar_paths(&tag).into_iter().filter(|path| path.symlink_metadata().is_ok()));
+            paths.sort();
+            paths.dedup();
+
+            info!("Found {} file(s) tagged '{}'", paths.len(), tag);
+            let title = format!("Tagged {} ({})", tag, paths.len());
+            let items = paths.into_iter().map(|path| (path, String::new())).collect();
+            self.state.open_dialog(Dialog::PathList(PathListDialog { title, items, selected: 0 }));
+        }
+
         AppReturn::Continue
     }
 
+    /// Called once the editor started by `AppReturn::RunEditor` exits
+    pub fn editor_closed(&mut self, status: io::Result<ExitStatus>) {
+        let (name_edit, note_edit) = (self.name_edit.take(), self.note_edit.take());
+
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                warn!("Editor exited with {}, nothing changed", status);
+                return;
+            }
+            Err(err) => {
+                error!("Cannot run editor: {}", err);
+                return;
+            }
+        }
+
+        if let Some(edit) = note_edit {
+            let result = edit.text().and_then(|text| self.notes.set(&edit.path, &text));
+            match result {
+                Ok(_) => info!("Saved note of {}", edit.path.display()),
+                Err(err) => error!("Note of {} not saved: {}", edit.path.display(), err),
+            }
+        }
+
+        let (edit, vfs) = match (name_edit, self.state.shared_vfs()) {
+            (Some(edit), Some(vfs)) => (edit, vfs),
+            _ => return,
+        };
+
+        match edit.changes(vfs.as_ref()) {
+            Ok(changes) if changes.is_empty() => info!("No names changed"),
+            Ok(changes) => {
+                self.state.open_dialog(Dialog::Confirm(ConfirmDialog {
+                    message: edit.summary(&changes),
+                    on_confirm: ConfirmAction::ApplyNameEdit(changes),
+                }));
+            }
+            Err(err) => error!("Names not changed: {}", err),
+        }
+    }
+
+    /// Move through the help with the arrows, returning whether `key` did,
+    /// the ui clamps it to the last page
+    fn scroll_help(&mut self, key: Key) -> bool {
+        let scroll = self.state.help_scroll();
+        let scroll = match key {
+            Key::Up => scroll.saturating_sub(1),
+            Key::Down => scroll.saturating_add(1),
+            Key::PageUp => scroll.saturating_sub(HELP_PAGE),
+            Key::PageDown => scroll.saturating_add(HELP_PAGE),
+            Key::Home => 0,
+            Key::End => u16::MAX,
+            _ => return false,
+        };
+
+        self.state.set_help_scroll(scroll);
+        true
+    }
+
+    fn do_dialog_action(&mut self, key: Key) {
+        let vfs = match self.state.shared_vfs() {
+            Some(vfs) => vfs,
+            None => return,
+        };
+
+        match self.state.dialog_mut() {
+            Some(Dialog::Conflict(dialog)) => {
+                let resolution = match key {
+                    Key::Char('o') => Resolution::Overwrite,
+                    Key::Char('s') => Resolution::Skip,
+                    Key::Char('r') => Resolution::Rename,
+                    Key::Char('n') => Resolution::OverwriteIfNewer,
+                    Key::Char('a') => {
+                        dialog.apply_all = !dialog.apply_all;
+                        return;
+                    }
+                    Key::Esc => {
+                        self.state.close_dialog();
+                        info!("Paste cancelled");
+                        return;
+                    }
+                    _ => return,
+                };
+
+                let count = if dialog.apply_all { dialog.pending.len() } else { 1 };
+                for transfer in dialog.pending.drain(..count) {
+                    if let Some(transfer) = resolution.resolve(vfs.as_ref(), transfer) {
+                        dialog.resolved.push(transfer);
+                    }
+                }
+
+                if dialog.pending.is_empty() {
+                    if let Some(Dialog::Conflict(dialog)) = self.state.close_dialog() {
+                        self.spawn_transfers(dialog.mode, dialog.resolved);
+                    }
+                }
+            }
+            Some(Dialog::Confirm(_)) => {
+                if let Some(Dialog::Confirm(dialog)) = self.state.close_dialog() {
+                    // removing an attribute goes back to the attribute panel either way
+                    let back_to = match &dialog.on_confirm {
+                        ConfirmAction::RemoveXattr(path, _) => Some(path.clone()),
+                        _ => None,
+                    };
+
+                    if key == Key::Char('y') {
+                        self.confirm(dialog.on_confirm);
+                    }
+
+                    if let Some(path) = back_to {
+                        self.open_xattrs(&path, 0);
+                    }
+                }
+            }
+            Some(Dialog::Input(dialog)) => {
+                match (key, &mut dialog.purpose) {
+                    (Key::Esc, InputPurpose::SetXattr { path, .. }) => {
+                        let path = path.clone();
+                        self.state.close_dialog();
+                        self.open_xattrs(&path, 0);
+                    }
+                    (Key::Esc, _) => {
+                        self.state.close_dialog();
+                    }
+                    (Key::Enter, _) => {
+                        if let Some(Dialog::Input(mut dialog)) = self.state.close_dialog() {
+                            // a rejected input stays open with the reason shown
+                            if let Err(err) = self.submit_input(dialog.input.text(), &dialog.purpose) {
+                                dialog.error = Some(err);
+                                self.state.open_dialog(Dialog::Input(dialog));
+                            }
+                        }
+                    }
+                    (Key::Tab, InputPurpose::MarkPattern { regex, .. }) => *regex = !*regex,
+                    (Key::Ctrl('r'), InputPurpose::MarkPattern { recursive, .. }) => *recursive = !*recursive,
+                    (Key::Tab, InputPurpose::NewFromTemplate { templates, index }) => {
+                        let name = |index: usize| templates[index].file_name().unwrap_or_default().to_string_lossy().to_string();
+
+                        // follow the template name unless the user already typed their own
+                        let follow = dialog.input.text() == name(*index);
+                        *index = (*index + 1) % templates.len();
+                        if follow {
+                            dialog.input = TextInput::new(&name(*index));
+                        }
+                    }
+                    _ => {
+                        if dialog.input.handle_key(key) {
+                            dialog.error = None;
+                        }
+                    }
+                }
+            }
+            Some(Dialog::BatchRename(dialog)) => {
+                match key {
+                    Key::Esc => {
+                        self.state.close_dialog();
+                    }
+                    Key::Enter => {
+                        if let Some(Dialog::BatchRename(dialog)) = self.state.close_dialog() {
+                            if let Err(err) = self.batch_rename(&dialog) {
+                                warn!("Batch rename not applied: {}", err);
+                                self.state.open_dialog(Dialog::BatchRename(dialog));
+                            }
+                        }
+                    }
+                    _ => dialog.handle_key(key),
+                }
+            }
+            Some(Dialog::Message(_)) => {
+                self.state.close_dialog();
+            }
+            Some(Dialog::Xattrs(dialog)) => {
+                match key {
+                    Key::Esc => {
+                        self.state.close_dialog();
+                    }
+                    Key::Up => dialog.selected = dialog.selected.saturating_sub(1),
+                    Key::Down => dialog.selected = (dialog.selected + 1).min(dialog.attrs.len().saturating_sub(1)),
+                    Key::Char('h') => dialog.hex = !dialog.hex,
+                    Key::Char('a') => {
+                        let purpose = InputPurpose::SetXattr { path: dialog.path.clone(), name: None };
+                        self.state.open_dialog(Dialog::Input(InputDialog::new("Add attribute", "user.", purpose)));
+                    }
+                    Key::Char('e') | Key::Enter => {
+                        if let Some(attr) = dialog.current() {
+                            let name = attr.name.to_string_lossy().to_string();
+                            let title = format!("Edit {}", name);
+                            let text = xattrs::edit_text(&attr.value);
+                            let purpose = InputPurpose::SetXattr { path: dialog.path.clone(), name: Some(name) };
+                            self.state.open_dialog(Dialog::Input(InputDialog::new(&title, &text, purpose)));
+                        }
+                    }
+                    Key::Char('d') | Key::Delete => {
+                        if let Some(attr) = dialog.current() {
+                            let message = format!("Remove attribute {}?", attr.name.to_string_lossy());
+                            let on_confirm = ConfirmAction::RemoveXattr(dialog.path.clone(), attr.name.clone());
+                            self.state.open_dialog(Dialog::Confirm(ConfirmDialog { message, on_confirm }));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some(Dialog::PathList(dialog)) => {
+                match key {
+                    Key::Esc => {
+                        self.state.close_dialog();
+                    }
+                    Key::Up => dialog.selected = dialog.selected.saturating_sub(1),
+                    Key::Down => dialog.selected = (dialog.selected + 1).min(dialog.items.len().saturating_sub(1)),
+                    Key::Enter => {
+                        if let Some((path, _)) = dialog.items.get(dialog.selected).cloned() {
+                            self.state.close_dialog();
+                            if let Err(err) = self.state.reveal(&path) {
+                                error!("Cannot show {}: {}", path.display(), err);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some(Dialog::Chmod(dialog)) => {
+                match key {
+                    Key::Esc => {
+                        self.state.close_dialog();
+                    }
+                    Key::Enter => {
+                        if let Some(Dialog::Chmod(dialog)) = self.state.close_dialog() {
+                            if let Err(err) = self.chmod(&dialog) {
+                                warn!("Permissions not changed: {}", err);
+                                self.state.open_dialog(Dialog::Chmod(dialog));
+                            }
+                        }
+                    }
+                    _ => dialog.handle_key(key),
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Rename every source as previewed, refusing if any row has a problem
+    fn batch_rename(&mut self, dialog: &BatchRenameDialog) -> Result<(), String> {
+        let vfs = match self.state.shared_vfs() {
+            Some(vfs) => vfs,
+            None => return Ok(()),
+        };
+        let rows = batch_rename::preview(vfs.as_ref(), &dialog.sources, &dialog.options())?;
+
+        if let Some(row) = rows.iter().find(|row| row.problem.is_some()) {
+            return Err(format!("{}: {}", row.from.display(), row.problem.as_ref().unwrap()));
+        }
+
+        let mut operations = Vec::new();
+        for row in rows.into_iter().filter(|row| !row.is_unchanged()) {
+            if let Err(err) = vfs.rename(&row.from, &row.to) {
+                // keep what was already renamed undoable as one step
+                error!("Cannot rename {}: {}", row.from.display(), err);
+                break;
+            }
+
+            operations.push(Operation::Rename { from: row.from, to: row.to });
+        }
+
+        info!("Batch renamed {} entr(ies)", operations.len());
+        self.journal.record(operations);
+        self.state.unmark_all();
+        self.state.refresh();
+
+        Ok(())
+    }
+
+    /// Write the marked names, or every name in the directory, for `$EDITOR`
+    fn edit_names(&mut self) -> AppReturn {
+        let cursor = match self.state.cursor() {
+            Some(cursor) => cursor.clone(),
+            None => return AppReturn::Continue,
+        };
+
+        let paths: Vec<PathBuf> = match (self.state.marked(), self.state.current_list()) {
+            (Some(marked), _) if !marked.is_empty() => marked.iter().cloned().collect(),
+            (_, Some(list)) => list.items.iter().map(|item| item.path.clone()).collect(),
+            _ => Vec::new(),
+        };
+
+        if paths.is_empty() {
+            return AppReturn::Continue;
+        }
+
+        match NameEdit::create(&cursor, paths) {
+            Ok(edit) => {
+                let file = edit.file.clone();
+                self.name_edit = Some(edit);
+                AppReturn::RunEditor(file)
+            }
+            Err(err) => {
+                error!("Cannot write names for the editor: {}", err);
+                AppReturn::Continue
+            }
+        }
+    }
+
+    /// Apply the dialog's rules to every target, as one undoable step
+    fn chmod(&mut self, dialog: &ChmodDialog) -> Result<(), String> {
+        let (rule, dir_rule) = dialog.rules()?;
+        let vfs = match self.state.shared_vfs() {
+            Some(vfs) => vfs,
+            None => return Ok(()),
+        };
+
+        let mut operations = Vec::new();
+        let mut errors = Vec::new();
+        for target in &dialog.targets {
+            permissions::chmod(vfs.as_ref(), target, &rule, &dir_rule, dialog.recursive, &mut operations, &mut errors);
+        }
+
+        for (path, err) in &errors {
+            error!("Cannot change permissions of {}: {}", path.display(), err);
+        }
+
+        info!("Changed permissions of {} entr(ies)", operations.len());
+        self.journal.record(operations);
+        self.state.refresh();
+
+        Ok(())
+    }
+
+    fn submit_input(&mut self, text: &str, purpose: &InputPurpose) -> Result<(), String> {
+        match purpose {
+            InputPurpose::MarkPattern { mark, regex, recursive } => {
+                let pattern = Pattern::parse(text, *regex)?;
+                let (cursor, vfs) = match (self.state.cursor(), self.state.vfs()) {
+                    (Some(cursor), Some(vfs)) => (cursor, vfs),
+                    _ => return Ok(()),
+                };
+
+                let paths = pattern.find(vfs, cursor, *recursive);
+                info!("{} {} entr(ies) matching '{}'", if *mark { "Marked" } else { "Unmarked" }, paths.len(), text);
+                self.state.set_marks(paths, *mark);
+
+                Ok(())
+            }
+            InputPurpose::Rename { from } => {
+                let name = text.trim();
+                if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+                    return Err(format!("'{}' is not a valid name", name));
+                }
+
+                let to = from.with_file_name(name);
+                if &to == from {
+                    return Ok(());
+                }
+
+                let vfs = match self.state.vfs() {
+                    Some(vfs) => vfs,
+                    None => return Ok(()),
+                };
+
+                if vfs.symlink_stat(&to).is_ok() {
+                    return Err(format!("{} already exists", name));
+                }
+
+                vfs.rename(from, &to).map_err(|err| format!("Cannot rename: {}", err))?;
+                info!("Renamed {} to {}", from.display(), to.display());
+
+                if self.state.marked().is_some_and(|marked| marked.contains(from)) {
+                    self.state.set_marks(vec![from.clone()], false);
+                    self.state.set_marks(vec![to.clone()], true);
+                }
+
+                self.journal.record(vec![Operation::Rename { from: from.clone(), to }]);
+
+                self.state.refresh();
+                self.state.select_name(OsStr::new(name));
+
+                Ok(())
+            }
+            InputPurpose::Chown { targets } => {
+                let (uid, gid) = self.owners.parse_owner(text)?;
+                self.chown(targets, uid, gid);
+
+                Ok(())
+            }
+            InputPurpose::SetXattr { path, name } => {
+                let (name, value) = match name {
+                    Some(name) => (name.clone(), text),
+                    None => match text.split_once('=') {
+                        Some((name, value)) if !name.trim().is_empty() => (xattrs::full_name(name.trim()), value),
+                        _ => return Err(String::from("Type name=value")),
+                    },
+                };
+
+                let value = xattrs::parse_value(value)?;
+                xattrs::set(path, &name, &value).map_err(|err| format!("Cannot set {}: {}", name, err))?;
+                info!("Set {} on {}", name, path.display());
+
+                let selected = xattrs::list(path).ok()
+                    .and_then(|attrs| attrs.iter().position(|attr| attr.name == name.as_str()))
+                    .unwrap_or(0);
+                self.open_xattrs(path, selected);
+
+                Ok(())
+            }
+            InputPurpose::Tags { targets } => {
+                for target in targets {
+                    let tags = tags::edit(&self.tags.tags(target), text);
+                    match self.tags.set_tags(target, &tags) {
+                        Ok(_) => info!("Tagged {} with '{}'", target.display(), tags.join(", ")),
+                        Err(err) => error!("Cannot tag {}: {}", target.display(), err),
+                    }
+                }
+
+                self.state.refresh();
+
+                Ok(())
+            }
+            InputPurpose::TagView => {
+                let tag = text.trim();
+                if tag.is_empty() {
+                    return Err(String::from("Type a tag"));
+                }
+
+                info!("Looking for files tagged '{}'", tag);
+                self.tag_search = Some(TagSearch::start(self.config.tag_roots.clone(), tag.to_string()));
+
+                Ok(())
+            }
+            InputPurpose::Compress { sources } => {
+                let cursor = self.state.cursor().cloned().unwrap_or_default();
+                let archive = cursor.join(text.trim());
+
+                if ArchiveFormat::from_path(&archive).is_none() {
+                    return Err(String::from("Name it .zip, .tar, .tar.gz or .tar.zst"));
+                }
+
+                if archive.symlink_metadata().is_ok() {
+                    return Err(format!("{} already exists", archive.display()));
+                }
+
+                if sources.iter().any(|source| archive.starts_with(source)) {
+                    return Err(String::from("The archive cannot go inside what it holds"));
+                }
+
+                self.jobs.spawn_compress(cursor, sources.clone(), archive);
+
+                Ok(())
+            }
+            InputPurpose::ExtractTo { archive } => {
+                let cursor = self.state.cursor().cloned().unwrap_or_default();
+                let dest = cursor.join(text.trim());
+
+                if dest.symlink_metadata().is_ok_and(|metadata| !metadata.is_dir()) {
+                    return Err(format!("{} is not a directory", dest.display()));
+                }
+
+                self.jobs.spawn_extract(archive.clone(), dest);
+
+                Ok(())
+            }
+            InputPurpose::NoteSearch => {
+                let items = self.notes.search(text.trim());
+                info!("Found {} note(s) with '{}'", items.len(), text.trim());
+
+                let title = format!("Notes with '{}' ({})", text.trim(), items.len());
+                self.state.open_dialog(Dialog::PathList(PathListDialog { title, items, selected: 0 }));
+
+                Ok(())
+            }
+            InputPurpose::New(entry) => self.create(text, entry),
+            InputPurpose::NewFromTemplate { templates, index } => {
+                self.create(text, &NewEntry::Template(templates[*index].clone()))
+            }
+        }
+    }
+
+    /// Change the owner of every target, keeping on past failures and listing them at the end
+    fn chown(&mut self, targets: &[PathBuf], uid: Option<u32>, gid: Option<u32>) {
+        let vfs = match self.state.shared_vfs() {
+            Some(vfs) => vfs,
+            None => return,
+        };
+
+        let mut operations = Vec::new();
+        let mut failures = Vec::new();
+
+        for target in targets {
+            match owners::chown(vfs.as_ref(), target, uid, gid) {
+                Ok(operation) => operations.extend(operation),
+                Err(err) => failures.push(format!("{}: {}", target.display(), err)),
+            }
+        }
+
+        info!("Changed owner of {} entr(ies)", operations.len());
+        self.journal.record(operations);
+        self.state.refresh();
+
+        if !failures.is_empty() {
+            self.state.open_dialog(Dialog::Message(MessageDialog {
+                title: format!("Owner not changed for {} of {} entr(ies)", failures.len(), targets.len()),
+                lines: failures,
+            }));
+        }
+    }
+
+    /// Create `text` under the cursor and select the entry it starts with
+    fn create(&mut self, text: &str, entry: &NewEntry) -> Result<(), String> {
+        let relative = create::parse_relative(text)?;
+        let (cursor, vfs) = match (self.state.cursor(), self.state.shared_vfs()) {
+            (Some(cursor), Some(vfs)) => (cursor.clone(), vfs),
+            _ => return Ok(()),
+        };
+
+        let mut operations = Vec::new();
+        let result = create::create(vfs.as_ref(), &cursor, &relative, entry, &mut operations);
+
+        // intermediate directories stay undoable even if the last part failed
+        self.journal.record(operations);
+        self.state.refresh();
+        if let Some(first) = relative.iter().find(|part| *part != ".") {
+            self.state.select_name(first);
+        }
+
+        match result {
+            Ok(_) => {
+                info!("Created {}", cursor.join(&relative).display());
+                Ok(())
+            }
+            Err(err) => Err(format!("Cannot create {}: {}", relative.display(), err)),
+        }
+    }
+
+    fn confirm(&mut self, action: ConfirmAction) {
+        let vfs = match self.state.shared_vfs() {
+            Some(vfs) => vfs,
+            None => return,
+        };
+
+        match action {
+            ConfirmAction::DeleteForever(paths) => {
+                for path in paths {
+                    match vfs.remove(&path) {
+                        Ok(_) => info!("Deleted {}", path.display()),
+                        Err(err) => error!("Cannot delete {}: {}", path.display(), err),
+                    }
+                }
+
+                self.state.unmark_all();
+                self.state.refresh();
+            }
+            ConfirmAction::PurgeTrashEntry(entry) => {
+                match trash::purge(vfs.as_ref(), &entry) {
+                    Ok(_) => info!("Deleted {} from trash", entry.original_path.display()),
+                    Err(err) => error!("Cannot delete {} from trash: {}", entry.original_path.display(), err),
+                }
+
+                self.reload_trash_view();
+            }
+            ConfirmAction::EmptyTrash => {
+                match trash::empty(vfs.as_ref()) {
+                    Ok(_) => info!("Trash emptied"),
+                    Err(err) => error!("Cannot empty trash: {}", err),
+                }
+
+                self.reload_trash_view();
+            }
+            ConfirmAction::RestoreAs(entry, target) => {
+                match trash::restore_to(vfs.as_ref(), &entry, &target) {
+                    Ok(_) => info!("Restored {}", target.display()),
+                    Err(err) => error!("Cannot restore {}: {}", target.display(), err),
+                }
+
+                self.reload_trash_view();
+            }
+            ConfirmAction::RemoveXattr(path, name) => {
+                match xattrs::remove(&path, &name) {
+                    Ok(_) => info!("Removed {} from {}", name.to_string_lossy(), path.display()),
+                    Err(err) => error!("Cannot remove {}: {}", name.to_string_lossy(), err),
+                }
+            }
+            ConfirmAction::ApplyNameEdit(changes) => {
+                let mut operations = Vec::new();
+
+                for change in changes {
+                    match change {
+                        NameChange::Rename { from, to } => match vfs.rename(&from, &to) {
+                            Ok(_) => {
+                                self.state.set_marks(vec![from.clone()], false);
+                                operations.push(Operation::Rename { from, to });
+                            }
+                            Err(err) => error!("Cannot rename {}: {}", from.display(), err),
+                        },
+                        NameChange::Trash(path) => match trash::trash(vfs.as_ref(), &path) {
+                            Ok(entry) => {
+                                self.state.set_marks(vec![path], false);
+                                operations.push(Operation::Trash { entry });
+                            }
+                            Err(err) => error!("Cannot trash {}: {}", path.display(), err),
+                        },
+                    }
+                }
+
+                info!("Applied {} change(s) from the editor", operations.len());
+                self.journal.record(operations);
+                self.state.refresh();
+            }
+        }
+    }
+
+    fn restore_selected(&mut self) {
+        let (entry, vfs) = match (self.state.selected_trash_entry(), self.state.shared_vfs()) {
+            (Some(entry), Some(vfs)) => (entry.clone(), vfs),
+            _ => return,
+        };
+
+        if vfs.symlink_stat(&entry.original_path).is_ok() {
+            // something new took the old place, offer a free name next to it instead
+            let target = free_name(vfs.as_ref(), &entry.original_path);
+            let message = format!(
+                "{} already exists, restore as {}?",
+                entry.original_path.display(),
+                target.file_name().unwrap_or_default().to_string_lossy(),
+            );
+
+            self.state.open_dialog(Dialog::Confirm(ConfirmDialog {
+                message,
+                on_confirm: ConfirmAction::RestoreAs(entry, target),
+            }));
+            return;
+        }
+
+        match trash::restore(vfs.as_ref(), &entry) {
+            Ok(_) => info!("Restored {}", entry.original_path.display()),
+            Err(err) => error!("Cannot restore {}: {}", entry.original_path.display(), err),
+        }
+
+        self.reload_trash_view();
+    }
+
+    /// Link every marked entry into the cursor directory
+    fn link_marked(&mut self, kind: LinkKind) {
+        let (cursor, vfs) = match (self.state.cursor(), self.state.shared_vfs()) {
+            (Some(cursor), Some(vfs)) => (cursor.clone(), vfs),
+            _ => return,
+        };
+
+        let targets: Vec<PathBuf> = match self.state.marked() {
+            Some(marked) if !marked.is_empty() => marked.iter().cloned().collect(),
+            _ => {
+                info!("Mark the entries to link, then go to the directory to link them into");
+                return;
+            }
+        };
+
+        let mut operations = Vec::new();
+        for target in targets {
+            match link::link(vfs.as_ref(), &target, &cursor, kind) {
+                Ok(operation) => {
+                    info!("Linked {}", target.display());
+                    operations.push(operation);
+                }
+                Err(err) => error!("Cannot link {}: {}", target.display(), err),
+            }
+        }
+
+        self.journal.record(operations);
+        self.state.unmark_all();
+        self.state.refresh();
+    }
+
+    /// Show the attribute panel of `path` with the attribute at `selected` highlighted
+    fn open_xattrs(&mut self, path: &Path, selected: usize) {
+        match xattrs::list(path) {
+            Ok(attrs) => {
+                let selected = selected.min(attrs.len().saturating_sub(1));
+                self.state.open_dialog(Dialog::Xattrs(XattrDialog { path: path.to_path_buf(), attrs, selected, hex: false }));
+            }
+            Err(err) => error!("Cannot read extended attributes of {}: {}", path.display(), err),
+        }
+    }
+
+    /// Select the real entry behind the selected symlink, in its own directory
+    fn jump_to_link_target(&mut self) {
+        let path = match self.state.selected() {
+            Some(item) if item.link.is_some() => item.path.clone(),
+            Some(item) => {
+                info!("{} is not a symlink", item.path.display());
+                return;
+            }
+            None => return,
+        };
+
+        let vfs = match self.state.shared_vfs() {
+            Some(vfs) => vfs,
+            None => return,
+        };
+
+        let result = link::resolve(vfs.as_ref(), &path)
+            .and_then(|target| self.state.reveal(&target).map(|_| target));
+
+        match result {
+            Ok(target) => info!("Jumped to {}", target.display()),
+            Err(err) => error!("Cannot follow {}: {}", path.display(), err),
+        }
+    }
+
+    fn reload_trash_view(&mut self) {
+        if let Err(err) = self.state.open_trash_view() {
+            error!("Cannot read trash: {}", err);
+        }
+    }
+
+    fn trash_selected(&mut self) {
+        let vfs = match self.state.shared_vfs() {
+            Some(vfs) => vfs,
+            None => return,
+        };
+
+        let mut operations = Vec::new();
+        for path in self.state.take_targets() {
+            match trash::trash(vfs.as_ref(), &path) {
+                Ok(entry) => {
+                    info!("Moved {} to trash", path.display());
+                    operations.push(Operation::Trash { entry });
+                }
+                Err(err) => error!("Cannot trash {}: {}", path.display(), err),
+            }
+        }
+
+        self.journal.record(operations);
+        self.state.refresh();
+    }
+
+    fn paste(&mut self) {
+        let (cursor, vfs) = match (self.state.cursor(), self.state.shared_vfs()) {
+            (Some(cursor), Some(vfs)) => (cursor.clone(), vfs),
+            _ => return,
+        };
+
+        let clipboard = match self.state.clipboard() {
+            Some(clipboard) => clipboard.clone(),
+            None => return,
+        };
+
+        let mut resolved = Vec::new();
+        let mut pending = VecDeque::new();
+        for from in clipboard.paths {
+            let to = cursor.join(from.file_name().unwrap());
+
+            if cursor.starts_with(&from) {
+                warn!("Cannot paste {} into itself", from.display());
+                continue;
+            }
+
+            let transfer = Transfer { from, to, overwrite: false };
+
+            if vfs.symlink_stat(&transfer.to).is_err() {
+                resolved.push(transfer);
+                continue;
+            }
+
+            match self.config.conflict_policy {
+                ConflictPolicy::Ask => pending.push_back(transfer),
+                ConflictPolicy::Always(resolution) => {
+                    resolved.extend(resolution.resolve(vfs.as_ref(), transfer));
+                }
+            }
+        }
+
+        if pending.is_empty() {
+            self.spawn_transfers(clipboard.mode, resolved);
+            return;
+        }
+
+        self.state.open_dialog(Dialog::Conflict(ConflictDialog {
+            mode: clipboard.mode,
+            resolved,
+            pending,
+            apply_all: false,
+        }));
+    }
+
+    fn spawn_transfers(&mut self, mode: TransferMode, transfers: Vec<Transfer>) {
+        if transfers.is_empty() {
+            info!("Nothing to paste");
+            return;
+        }
+
+        // a copy can be pasted again, a cut is consumed once it runs so a
+        // cancelled conflict prompt keeps it
+        if mode == TransferMode::Move {
+            self.state.take_clipboard();
+        }
+
+        if let Some(vfs) = self.state.shared_vfs() {
+            self.jobs.spawn(vfs, mode, transfers, self.config.preserve_xattrs);
+        }
+    }
+
+    /// Enter `archive`, extracting it in the background first unless it already is
+    fn browse_archive(&mut self, archive: PathBuf) {
+        let root = match archive::browse_dir(&archive) {
+            Ok(root) => root,
+            Err(err) => {
+                error!("Cannot open {}: {}", archive.display(), err);
+                return;
+            }
+        };
+
+        if root.is_dir() {
+            self.state.enter_archive(&archive, root);
+            return;
+        }
+
+        if self.pending_browse.as_ref().is_some_and(|pending| pending.archive == archive) {
+            info!("Still extracting {}", archive.display());
+            return;
+        }
+
+        info!("Extracting {} to browse it, see the job list", archive.display());
+        let job = self.jobs.spawn_browse(archive.clone(), root.clone());
+        self.pending_browse = Some(PendingBrowse { job, archive, root });
+    }
+
     pub fn state(&self) -> &AppState {
         &self.state
     }
+
+    pub fn jobs(&self) -> &JobRegistry {
+        &self.jobs
+    }
 }
\ No newline at end of file
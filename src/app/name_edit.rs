@@ -0,0 +1,291 @@
+//! Bulk rename by editing a list of names in `$EDITOR`, the way vidir does.
+//!
+//! Every entry is written as `<number><tab><name>`. A line whose name was
+//! edited becomes a rename, a line that was removed sends the entry to the
+//! trash. The numbers tie each line back to its entry, so reordering is fine.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::Command;
+use std::time::SystemTime;
+
+use log::warn;
+
+use crate::app::vfs::Vfs;
+
+/// Lines of the summary listing individual changes
+const SUMMARY_LINES: usize = 8;
+
+/// Private directories tried before giving up on the temp directory
+const MAX_ATTEMPTS: u32 = 100;
+
+#[derive(Debug, Clone)]
+pub enum NameChange {
+    Rename { from: PathBuf, to: PathBuf },
+    Trash(PathBuf),
+}
+
+/// Names handed to the editor, waiting for it to exit
+pub struct NameEdit {
+    /// Temporary file the editor works on, removed on drop
+    pub file: PathBuf,
+    /// Names are written relative to this directory
+    base: PathBuf,
+    /// Entries with the exact text written for them
+    entries: Vec<(PathBuf, String)>,
+}
+
+impl NameEdit {
+    pub fn create(base: &Path, paths: Vec<PathBuf>) -> io::Result<Self> {
+        let mut entries = Vec::new();
+
+        for path in paths {
+            let text = path.strip_prefix(base).unwrap_or(&path).to_string_lossy().to_string();
+
+            // one entry per line, there is no way to edit such a name
+            if text.contains('\n') {
+                warn!("Cannot edit {} in an editor, its name has a line break", path.display());
+                continue;
+            }
+
+            entries.push((path, text));
+        }
+
+        let mut content = String::new();
+        for (index, (_, text)) in entries.iter().enumerate() {
+            content.push_str(&format!("{}\t{}\n", index + 1, text));
+        }
+
+        let file = editor_file("names.txt", &content)?;
+
+        Ok(Self { file, base: base.to_path_buf(), entries })
+    }
+
+    /// Read the edited file back and work out what changed
+    pub fn changes(&self, vfs: &dyn Vfs) -> Result<Vec<NameChange>, String> {
+        let content = fs::read_to_string(&self.file).map_err(|err| format!("Cannot read {}: {}", self.file.display(), err))?;
+
+        let mut edited: HashMap<usize, String> = HashMap::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let index = line.split_once('\t')
+                .and_then(|(index, text)| Some((index.trim().parse::<usize>().ok()?, text)))
+                .filter(|(index, _)| (1..=self.entries.len()).contains(index));
+
+            let (index, text) = match index {
+                Some(found) => found,
+                None => return Err(format!("line {}: expected '<number><tab><name>'", number + 1)),
+            };
+
+            if edited.insert(index - 1, text.to_string()).is_some() {
+                return Err(format!("line {}: number {} appears twice", number + 1, index));
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (index, (from, text)) in self.entries.iter().enumerate() {
+            match edited.get(&index) {
+                None => changes.push(NameChange::Trash(from.clone())),
+                Some(new_text) if new_text == text => {}
+                Some(new_text) => {
+                    let to = self.base.join(new_text);
+                    if new_text.is_empty() || to.file_name().is_none() {
+                        return Err(format!("'{}' is not a valid name", new_text));
+                    }
+
+                    changes.push(NameChange::Rename { from: from.clone(), to });
+                }
+            }
+        }
+
+        self.check_targets(vfs, &changes)?;
+
+        Ok(changes)
+    }
+
+    /// Renames are applied one by one, so any target already taken is refused
+    fn check_targets(&self, vfs: &dyn Vfs, changes: &[NameChange]) -> Result<(), String> {
+        let sources: HashSet<&PathBuf> = self.entries.iter().map(|(path, _)| path).collect();
+        let mut targets = HashSet::new();
+
+        for change in changes {
+            if let NameChange::Rename { to, .. } = change {
+                if !targets.insert(to) {
+                    return Err(format!("{} is the target of two renames", to.display()));
+                }
+                else if sources.contains(to) {
+                    return Err(format!("{} is taken by another entry, rename it in two passes", to.display()));
+                }
+                else if vfs.symlink_stat(to).is_ok() {
+                    return Err(format!("{} already exists", to.display()));
+                }
+                else if !to.parent().is_some_and(|parent| vfs.stat(parent).is_ok_and(|stat| stat.is_dir())) {
+                    return Err(format!("{} is not in an existing directory", to.display()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Question asked before applying, listing the first few changes
+    pub fn summary(&self, changes: &[NameChange]) -> String {
+        let renames = changes.iter().filter(|change| matches!(change, NameChange::Rename { .. })).count();
+        let trashes = changes.len() - renames;
+
+        let mut summary = format!("Rename {} and trash {} entr(ies)?", renames, trashes);
+        for change in changes.iter().take(SUMMARY_LINES) {
+            let line = match change {
+                NameChange::Rename { from, to } => format!("{} -> {}", self.relative(from), self.relative(to)),
+                NameChange::Trash(path) => format!("trash {}", self.relative(path)),
+            };
+
+            summary.push('\n');
+            summary.push_str(&line);
+        }
+
+        if changes.len() > SUMMARY_LINES {
+            summary.push_str(&format!("\n... and {} more", changes.len() - SUMMARY_LINES));
+        }
+
+        summary
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.base).unwrap_or(path).to_string_lossy().to_string()
+    }
+}
+
+impl Drop for NameEdit {
+    fn drop(&mut self) {
+        remove_editor_file(&self.file);
+    }
+}
+
+/// Write `content` to `name` in a new directory under the temp directory that
+/// only this user can enter, so nobody else can plant a link there or swap the
+/// text before it is read back
+pub fn editor_file(name: &str, content: &str) -> io::Result<PathBuf> {
+    for attempt in 0..MAX_ATTEMPTS {
+        let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+        let dir = env::temp_dir().join(format!("file_tui-{}-{:x}{}", process::id(), nanos, attempt));
+
+        match fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+
+        let file = dir.join(name);
+        let result = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&file)
+            .and_then(|mut writer| writer.write_all(content.as_bytes()));
+
+        if let Err(err) = result {
+            remove_editor_file(&file);
+            return Err(err);
+        }
+
+        return Ok(file);
+    }
+
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "No free name for a private temporary directory"))
+}
+
+/// Remove a file made by `editor_file` along with its directory
+pub fn remove_editor_file(file: &Path) {
+    let _ = fs::remove_file(file);
+
+    if let Some(dir) = file.parent() {
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+/// `$VISUAL`, then `$EDITOR`, then vi, opened on `file`
+pub fn editor_command(file: &Path) -> Command {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"));
+
+    // the variable may carry arguments, like "code --wait"
+    let mut words = editor.split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or("vi"));
+    command.args(words).arg(file);
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::app::vfs::MemoryFs;
+
+    const FIXTURE: &str = "dir/a.txt\ndir/b.txt\ndir/sub/c.txt\ndir/taken";
+
+    /// Changes after the editor left `edited` in the file, as `from -> to` or `trash path`
+    fn changes(edited: &str) -> Result<Vec<String>, String> {
+        let vfs = MemoryFs::from_fixture(FIXTURE).unwrap();
+        let paths = ["/dir/a.txt", "/dir/b.txt", "/dir/sub/c.txt"].map(PathBuf::from).to_vec();
+        let edit = NameEdit::create(Path::new("/dir"), paths).unwrap();
+        fs::write(&edit.file, edited).unwrap();
+
+        let changes = edit.changes(&vfs)?;
+        Ok(changes.iter()
+            .map(|change| match change {
+                NameChange::Rename { from, to } => format!("{} -> {}", from.display(), to.display()),
+                NameChange::Trash(path) => format!("trash {}", path.display()),
+            })
+            .collect())
+    }
+
+    #[test]
+    fn names_are_written_numbered_and_relative() {
+        let edit = NameEdit::create(Path::new("/dir"), vec![PathBuf::from("/dir/a"), PathBuf::from("/dir/sub/b")]).unwrap();
+
+        assert_eq!(fs::read_to_string(&edit.file).unwrap(), "1\ta\n2\tsub/b\n");
+        assert_eq!(edit.file.metadata().unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(edit.file.parent().unwrap().metadata().unwrap().permissions().mode() & 0o777, 0o700);
+
+        let file = edit.file.clone();
+        drop(edit);
+        assert!(!file.exists() && !file.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn edited_lines_become_renames_and_missing_ones_trash() {
+        assert_eq!(changes("1\ta.txt\n2\tb.txt\n3\tsub/c.txt\n").unwrap(), Vec::<String>::new());
+        assert_eq!(
+            changes("3\tsub/d.txt\r\n\n1\tsub/a.txt\n").unwrap(),
+            ["/dir/a.txt -> /dir/sub/a.txt", "trash /dir/b.txt", "/dir/sub/c.txt -> /dir/sub/d.txt"],
+        );
+    }
+
+    #[test]
+    fn bad_lines_and_targets_are_refused() {
+        assert_eq!(changes("1 a.txt").unwrap_err(), "line 1: expected '<number><tab><name>'");
+        assert_eq!(changes("4\tx").unwrap_err(), "line 1: expected '<number><tab><name>'");
+        assert_eq!(changes("1\tx\n1\ty").unwrap_err(), "line 2: number 1 appears twice");
+        assert_eq!(changes("1\t").unwrap_err(), "'' is not a valid name");
+        assert_eq!(changes("1\tz\n2\tz").unwrap_err(), "/dir/z is the target of two renames");
+        assert_eq!(changes("1\tb.txt\n2\tx").unwrap_err(), "/dir/b.txt is taken by another entry, rename it in two passes");
+        assert_eq!(changes("1\ttaken").unwrap_err(), "/dir/taken already exists");
+        assert_eq!(changes("1\tnew/a.txt").unwrap_err(), "/dir/new/a.txt is not in an existing directory");
+    }
+}
@@ -0,0 +1,212 @@
+//! Multi-line notes on files and directories, kept under the XDG data directory.
+//!
+//! A note is keyed by the path it was written for and by the device and inode
+//! behind it. The path wins, so editors that save by replacing a file keep its
+//! note. The inode finds the note again once the entry was renamed or moved
+//! within its filesystem and the old path is gone.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::app::config::data_home;
+use crate::app::name_edit::{editor_file, remove_editor_file};
+use crate::app::state::File;
+use crate::app::trash::{decode_path, encode_path};
+use crate::app::vfs::Stat;
+
+#[derive(Debug, Clone)]
+struct Note {
+    dev: u64,
+    ino: u64,
+    text: String,
+}
+
+#[derive(Default)]
+pub struct NoteStore {
+    notes: BTreeMap<PathBuf, Note>,
+    /// Path each note is stored under, by device and inode
+    by_inode: HashMap<(u64, u64), PathBuf>,
+    /// File the notes are saved to, `None` keeps them in memory only
+    path: Option<PathBuf>,
+}
+
+impl NoteStore {
+    pub fn load() -> Self {
+        let path = data_home().join("file_tui").join("notes");
+        let mut notes = BTreeMap::new();
+
+        // dev:ino<tab>path<tab>text, with the text escaped onto one line
+        for line in fs::read_to_string(&path).unwrap_or_default().lines() {
+            let mut fields = line.splitn(3, '\t');
+            let note = match (fields.next(), fields.next(), fields.next()) {
+                (Some(id), Some(file), Some(text)) => id.split_once(':')
+                    .and_then(|(dev, ino)| Some((dev.parse().ok()?, ino.parse().ok()?)))
+                    .map(|(dev, ino)| (decode_path(file), Note { dev, ino, text: unescape(text) })),
+                _ => None,
+            };
+
+            match note {
+                Some((file, note)) => {
+                    notes.insert(file, note);
+                }
+                None => warn!("Notes: ignoring malformed line '{}'", line),
+            }
+        }
+
+        let mut store = Self { notes, by_inode: HashMap::new(), path: Some(path) };
+        store.index();
+        store
+    }
+
+    /// Note of the entry at `path`, followed through renames by its inode
+    pub fn note(&self, path: &Path, stat: &Stat) -> Option<&str> {
+        self.key(path, stat).map(|key| self.notes[&key].text.as_str())
+    }
+
+    /// Replace the note of `path`, an empty text removes it
+    pub fn set(&mut self, path: &Path, text: &str) -> io::Result<()> {
+        let stat = Stat::from(&path.symlink_metadata()?);
+
+        if let Some(key) = self.key(path, &stat) {
+            self.notes.remove(&key);
+        }
+
+        let text = text.trim_end();
+        if !text.is_empty() {
+            let note = Note { dev: stat.dev, ino: stat.ino, text: text.to_string() };
+            self.notes.insert(path.to_path_buf(), note);
+        }
+
+        self.index();
+        self.save()
+    }
+
+    /// Store notes found by inode in `files` under their new path
+    pub fn follow_renames(&mut self, files: &[File]) {
+        let mut renamed = Vec::new();
+        for file in files {
+            match self.key(&file.path, &file.metadata) {
+                Some(key) if key != file.path => renamed.push((key, file.path.clone())),
+                _ => {}
+            }
+        }
+
+        if renamed.is_empty() {
+            return;
+        }
+
+        for (from, to) in renamed {
+            info!("Note of {} follows it to {}", from.display(), to.display());
+            if let Some(note) = self.notes.remove(&from) {
+                self.notes.insert(to, note);
+            }
+        }
+
+        self.index();
+        if let Err(err) = self.save() {
+            warn!("Cannot save notes: {}", err);
+        }
+    }
+
+    /// Notes containing `query`, ignoring case, with the first line that matched
+    pub fn search(&self, query: &str) -> Vec<(PathBuf, String)> {
+        let query = query.to_lowercase();
+
+        self.notes.iter()
+            .filter_map(|(path, note)| {
+                let line = note.text.lines().find(|line| line.to_lowercase().contains(&query))?;
+                Some((path.clone(), line.trim().to_string()))
+            })
+            .collect()
+    }
+
+    /// Path the note of the entry is stored under
+    fn key(&self, path: &Path, stat: &Stat) -> Option<PathBuf> {
+        if self.notes.contains_key(path) {
+            return Some(path.to_path_buf());
+        }
+
+        // the inode may have been reused since, only trust it once the old path is gone
+        self.by_inode.get(&(stat.dev, stat.ino))
+            .filter(|stored| stored.symlink_metadata().is_err())
+            .cloned()
+    }
+
+    fn index(&mut self) {
+        self.by_inode = self.notes.iter()
+            .map(|(path, note)| ((note.dev, note.ino), path.clone()))
+            .collect();
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut content = String::new();
+        for (path, note) in &self.notes {
+            content.push_str(&format!("{}:{}\t{}\t{}\n", note.dev, note.ino, encode_path(path), escape(&note.text)));
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// Note of one entry handed to the editor, waiting for it to exit
+pub struct NoteEdit {
+    /// Temporary file the editor works on, removed on drop
+    pub file: PathBuf,
+    /// Entry the note belongs to
+    pub path: PathBuf,
+}
+
+impl NoteEdit {
+    pub fn create(path: &Path, text: &str) -> io::Result<Self> {
+        let file = editor_file("note.txt", text)?;
+
+        Ok(Self { file, path: path.to_path_buf() })
+    }
+
+    pub fn text(&self) -> io::Result<String> {
+        fs::read_to_string(&self.file)
+    }
+}
+
+impl Drop for NoteEdit {
+    fn drop(&mut self) {
+        remove_editor_file(&self.file);
+    }
+}
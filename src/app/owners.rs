@@ -0,0 +1,96 @@
+//! User and group names from `/etc/passwd` and `/etc/group`, read once.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::app::journal::Operation;
+use crate::app::vfs::Vfs;
+
+#[derive(Debug, Clone, Default)]
+pub struct Owners {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Owners {
+    pub fn load() -> Self {
+        Self {
+            users: read_names(Path::new("/etc/passwd")),
+            groups: read_names(Path::new("/etc/group")),
+        }
+    }
+
+    /// Name of the user, or the bare uid when it has none
+    pub fn user(&self, uid: u32) -> String {
+        self.users.get(&uid).cloned().unwrap_or_else(|| uid.to_string())
+    }
+
+    pub fn group(&self, gid: u32) -> String {
+        self.groups.get(&gid).cloned().unwrap_or_else(|| gid.to_string())
+    }
+
+    /// Parse `user`, `user:group` or `:group`, names or numeric ids
+    pub fn parse_owner(&self, text: &str) -> Result<(Option<u32>, Option<u32>), String> {
+        let (user, group) = match text.trim().split_once(':') {
+            Some((user, group)) => (user, group),
+            None => (text.trim(), ""),
+        };
+
+        let uid = lookup(&self.users, user).map_err(|_| format!("No user called '{}'", user))?;
+        let gid = lookup(&self.groups, group).map_err(|_| format!("No group called '{}'", group))?;
+
+        if uid.is_none() && gid.is_none() {
+            return Err(String::from("Give a user, a group or both as user:group"));
+        }
+
+        Ok((uid, gid))
+    }
+}
+
+/// `name:password:id:...` lines mapped by id
+fn read_names(path: &Path) -> HashMap<u32, String> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+
+    content.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((id, name.to_string()))
+        })
+        .collect()
+}
+
+fn lookup(names: &HashMap<u32, String>, text: &str) -> Result<Option<u32>, ()> {
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    if let Ok(id) = text.parse() {
+        return Ok(Some(id));
+    }
+
+    names.iter()
+        .find(|(_, name)| name.as_str() == text)
+        .map(|(id, _)| Some(*id))
+        .ok_or(())
+}
+
+/// Change the owner of `path` itself, a symlink is not followed.
+///
+/// Returns `None` when the owner was already the requested one.
+pub fn chown(vfs: &dyn Vfs, path: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<Option<Operation>> {
+    let stat = vfs.symlink_stat(path)?;
+    let old = (stat.uid, stat.gid);
+    let new = (uid.unwrap_or(old.0), gid.unwrap_or(old.1));
+
+    if new == old {
+        return Ok(None);
+    }
+
+    vfs.set_owner(path, new.0, new.1)?;
+
+    Ok(Some(Operation::Chown { path: path.to_path_buf(), old, new }))
+}
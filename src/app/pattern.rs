@@ -0,0 +1,102 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::app::vfs::Vfs;
+
+/// File name matcher written as a shell glob or a regex
+pub enum Pattern {
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn parse(text: &str, regex: bool) -> Result<Self, String> {
+        if regex {
+            Regex::new(text).map(Pattern::Regex).map_err(|err| err.to_string())
+        }
+        else {
+            glob::Pattern::new(text).map(Pattern::Glob).map_err(|err| err.to_string())
+        }
+    }
+
+    /// Globs must match the whole name, regexes anywhere in it unless anchored
+    pub fn matches(&self, name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+
+        match self {
+            Pattern::Glob(glob) => glob.matches(&name),
+            Pattern::Regex(regex) => regex.is_match(&name),
+        }
+    }
+
+    /// Paths of the entries in `dir` whose name matches, optionally descending into subdirectories
+    pub fn find(&self, vfs: &dyn Vfs, dir: &Path, recursive: bool) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+
+        let entries = match vfs.list(dir) {
+            Ok(entries) => entries,
+            Err(_) => return found,
+        };
+
+        for path in entries {
+            if self.matches(path.file_name().unwrap_or_default()) {
+                found.push(path.clone());
+            }
+
+            // symlinked directories are not followed so a loop can't hang us
+            if recursive && vfs.symlink_stat(&path).is_ok_and(|stat| stat.is_dir()) {
+                found.extend(self.find(vfs, &path, recursive));
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::vfs::MemoryFs;
+
+    fn matches(text: &str, regex: bool, name: &str) -> bool {
+        Pattern::parse(text, regex).unwrap().matches(OsStr::new(name))
+    }
+
+    #[test]
+    fn globs_match_the_whole_name() {
+        assert!(matches("*.rs", false, "main.rs"));
+        assert!(!matches("*.rs", false, "main.rs.bak"));
+        assert!(matches("file-?.[ch]", false, "file-1.h"));
+        assert!(!matches("main", false, "main.rs"));
+    }
+
+    #[test]
+    fn regexes_match_anywhere_unless_anchored() {
+        assert!(matches(r"\d+", true, "report-2024.pdf"));
+        assert!(matches("main", true, "main.rs"));
+        assert!(!matches("^rs", true, "main.rs"));
+        assert!(!matches("main", true, "Main.rs"));
+    }
+
+    #[test]
+    fn bad_patterns_are_errors() {
+        assert!(Pattern::parse("[", false).is_err());
+        assert!(Pattern::parse("(", true).is_err());
+    }
+
+    #[test]
+    fn find_descends_without_following_symlinks() {
+        let vfs = MemoryFs::from_fixture("a.txt\nsub/b.txt\nsub/c.md\nlink -> sub").unwrap();
+        let pattern = Pattern::parse("*.txt", false).unwrap();
+
+        let mut found = pattern.find(&vfs, Path::new("/"), false);
+        found.sort();
+        assert_eq!(found, [PathBuf::from("/a.txt")]);
+
+        let mut found = pattern.find(&vfs, Path::new("/"), true);
+        found.sort();
+        assert_eq!(found, [PathBuf::from("/a.txt"), PathBuf::from("/sub/b.txt")]);
+    }
+}
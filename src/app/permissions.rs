@@ -0,0 +1,235 @@
+//! Mode bits as octal values or chmod style symbolic expressions.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::app::journal::Operation;
+use crate::app::vfs::Vfs;
+
+const USER: u32 = 0o4700;
+const GROUP: u32 = 0o2070;
+const OTHER: u32 = 0o1007;
+
+/// New mode for an entry, worked out from its current one
+#[derive(Debug, Clone)]
+pub enum ModeRule {
+    Octal(u32),
+    /// Clauses like `u+x` or `go=r`, applied left to right
+    Symbolic(Vec<Clause>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Clause {
+    /// Bits of the classes the clause is about
+    who: u32,
+    ops: Vec<(char, String)>,
+}
+
+impl ModeRule {
+    /// Parse `755` or `u+x,go-w`, an empty class means all of them
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+
+        if !text.is_empty() && text.len() <= 4 && text.chars().all(|c| ('0'..='7').contains(&c)) {
+            return Ok(ModeRule::Octal(u32::from_str_radix(text, 8).unwrap()));
+        }
+
+        let mut clauses = Vec::new();
+        for part in text.split(',') {
+            clauses.push(parse_clause(part).ok_or_else(|| format!("'{}' is not an octal or symbolic mode", part))?);
+        }
+
+        Ok(ModeRule::Symbolic(clauses))
+    }
+
+    pub fn apply(&self, mode: u32, is_dir: bool) -> u32 {
+        let mut mode = mode & 0o7777;
+
+        let clauses = match self {
+            ModeRule::Octal(value) => return *value,
+            ModeRule::Symbolic(clauses) => clauses,
+        };
+
+        for clause in clauses {
+            for (op, perms) in &clause.ops {
+                let bits = perm_bits(perms, clause.who, mode, is_dir);
+
+                match op {
+                    '+' => mode |= bits,
+                    '-' => mode &= !bits,
+                    _ => mode = (mode & !clause.who) | bits,
+                }
+            }
+        }
+
+        mode
+    }
+}
+
+fn parse_clause(part: &str) -> Option<Clause> {
+    let op_start = part.find(['+', '-', '='])?;
+    let (classes, mut rest) = part.split_at(op_start);
+
+    let mut who = 0;
+    for class in classes.chars() {
+        who |= match class {
+            'u' => USER,
+            'g' => GROUP,
+            'o' => OTHER,
+            'a' => USER | GROUP | OTHER,
+            _ => return None,
+        };
+    }
+    if who == 0 {
+        who = USER | GROUP | OTHER;
+    }
+
+    let mut ops = Vec::new();
+    while let Some(op) = rest.chars().next() {
+        let perms_end = rest[1..].find(['+', '-', '=']).map_or(rest.len(), |index| index + 1);
+        let perms = &rest[1..perms_end];
+
+        if !matches!(op, '+' | '-' | '=') || !perms.chars().all(|c| "rwxXst".contains(c)) {
+            return None;
+        }
+
+        ops.push((op, perms.to_string()));
+        rest = &rest[perms_end..];
+    }
+
+    Some(Clause { who, ops })
+}
+
+/// Bits named by `perms`, limited to the classes in `who`
+fn perm_bits(perms: &str, who: u32, mode: u32, is_dir: bool) -> u32 {
+    let mut bits = 0;
+
+    for perm in perms.chars() {
+        bits |= match perm {
+            'r' => 0o444,
+            'w' => 0o222,
+            'x' => 0o111,
+            // execute only where it makes sense: directories, or files someone can already run
+            'X' if is_dir || mode & 0o111 != 0 => 0o111,
+            's' => 0o6000,
+            't' => 0o1000,
+            _ => 0,
+        };
+    }
+
+    bits & who
+}
+
+/// `rwxr-x---` with the setuid, setgid and sticky bits folded in like `ls`
+pub fn format_mode(mode: u32) -> String {
+    let mut text = String::new();
+
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        text.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+
+    text
+}
+
+/// Apply the rules to `path`, and below it when `recursive`.
+///
+/// Symlinks are skipped since chmod would change what they point to. Every
+/// change is pushed to `operations`, failures are collected in `errors`.
+pub fn chmod(
+    vfs: &dyn Vfs,
+    path: &Path,
+    file_rule: &ModeRule,
+    dir_rule: &ModeRule,
+    recursive: bool,
+    operations: &mut Vec<Operation>,
+    errors: &mut Vec<(PathBuf, io::Error)>,
+) {
+    let stat = match vfs.symlink_stat(path) {
+        Ok(stat) => stat,
+        Err(err) => {
+            errors.push((path.to_path_buf(), err));
+            return;
+        }
+    };
+
+    if stat.is_symlink() {
+        return;
+    }
+
+    let is_dir = stat.is_dir();
+    let old_mode = stat.mode;
+    let new_mode = if is_dir { dir_rule } else { file_rule }.apply(old_mode, is_dir);
+
+    if new_mode != old_mode {
+        match vfs.set_mode(path, new_mode) {
+            Ok(_) => operations.push(Operation::Chmod { path: path.to_path_buf(), old_mode, new_mode }),
+            Err(err) => errors.push((path.to_path_buf(), err)),
+        }
+    }
+
+    if recursive && is_dir {
+        match vfs.list(path) {
+            Ok(entries) => {
+                for entry in entries {
+                    chmod(vfs, &entry, file_rule, dir_rule, recursive, operations, errors);
+                }
+            }
+            Err(err) => errors.push((path.to_path_buf(), err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(rule: &str, mode: u32, is_dir: bool) -> u32 {
+        ModeRule::parse(rule).unwrap().apply(mode, is_dir)
+    }
+
+    #[test]
+    fn octal_replaces_the_mode() {
+        assert_eq!(apply("640", 0o100755, false), 0o640);
+        assert_eq!(apply(" 4755 ", 0o644, false), 0o4755);
+    }
+
+    #[test]
+    fn symbolic_clauses_apply_in_order() {
+        assert_eq!(apply("u+x", 0o644, false), 0o744);
+        assert_eq!(apply("go-w", 0o666, false), 0o644);
+        assert_eq!(apply("u=rw,o=", 0o757, false), 0o650);
+        assert_eq!(apply("+x", 0o600, false), 0o711);
+        assert_eq!(apply("a-x+r", 0o711, false), 0o644);
+        assert_eq!(apply("u+s,o+t", 0o755, false), 0o5755);
+    }
+
+    #[test]
+    fn capital_x_only_for_directories_and_runnable_files() {
+        assert_eq!(apply("a+X", 0o644, false), 0o644);
+        assert_eq!(apply("a+X", 0o744, false), 0o755);
+        assert_eq!(apply("a+X", 0o700, true), 0o711);
+    }
+
+    #[test]
+    fn bad_modes_are_rejected() {
+        for text in ["", "9", "u+q", "z+x", "u", "644,"] {
+            assert!(ModeRule::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn modes_are_formatted_like_ls() {
+        assert_eq!(format_mode(0o754), "rwxr-xr--");
+        assert_eq!(format_mode(0o4755), "rwsr-xr-x");
+        assert_eq!(format_mode(0o2640), "rw-r-S---");
+        assert_eq!(format_mode(0o1777), "rwxrwxrwt");
+    }
+}
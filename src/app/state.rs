@@ -1,17 +1,95 @@
-use std::collections::HashMap;
-use std::ffi::OsString;
-use std::fs::{FileType, Metadata, ReadDir};
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use log::{error, info};
 use tui::widgets::{ListState};
+use crate::app::dialog::Dialog;
+use crate::app::jobs::TransferMode;
 use crate::app::state::AppState::Initialized;
+use crate::app::tags;
+use crate::app::trash;
+use crate::app::trash::TrashEntry;
+use crate::app::vfs::{LocalFs, Stat, Vfs};
 
 #[derive(Clone)]
 pub struct File {
     pub name: OsString,
     pub path: PathBuf,
-    pub file_type: FileType,
     pub is_dir: bool,
-    pub metadata: Metadata,
+    /// Stat of the entry itself, a symlink is not followed
+    pub metadata: Stat,
+    /// Set when the entry is a symlink
+    pub link: Option<SymlinkInfo>,
+    /// Tags from the entry's xattr, sidecar tags are looked up separately
+    pub tags: Vec<String>,
+}
+
+impl File {
+    /// Read an entry without following it if it is a symlink
+    pub fn from_path(vfs: &dyn Vfs, path: PathBuf) -> io::Result<File> {
+        let metadata = vfs.symlink_stat(&path)?;
+
+        let link = if metadata.is_symlink() {
+            Some(SymlinkInfo {
+                target: vfs.read_link(&path)?,
+                resolved: vfs.stat(&path).ok(),
+            })
+        }
+        else {
+            None
+        };
+
+        // a link to a directory can be entered like the directory itself
+        let is_dir = match &link {
+            Some(link) => link.resolved.as_ref().is_some_and(Stat::is_dir),
+            None => metadata.is_dir(),
+        };
+
+        Ok(File {
+            name: path.file_name().unwrap_or_default().to_os_string(),
+            is_dir,
+            metadata,
+            link,
+            tags: tags::read_xattr(vfs, &path).unwrap_or_default(),
+            path,
+        })
+    }
+
+    pub fn is_broken_link(&self) -> bool {
+        self.link.as_ref().is_some_and(|link| link.resolved.is_none())
+    }
+}
+
+#[derive(Clone)]
+pub struct SymlinkInfo {
+    /// Path stored in the link, as written
+    pub target: PathBuf,
+    /// Stat of what the link finally points to, `None` when the link is broken
+    pub resolved: Option<Stat>,
+}
+
+/// Entries of a directory, skipping those that vanish while being read
+fn read_entries(vfs: &dyn Vfs, dir: &Path) -> io::Result<Vec<File>> {
+    Ok(vfs.list(dir)?
+        .into_iter()
+        .filter_map(|path| File::from_path(vfs, path).ok())
+        .collect())
+}
+
+/// An archive being browsed, its contents extracted to `root`
+#[derive(Clone)]
+pub struct ArchiveMount {
+    pub archive: PathBuf,
+    pub root: PathBuf,
+}
+
+/// Paths yanked or cut, waiting to be pasted into the cursor directory
+#[derive(Clone)]
+pub struct Clipboard {
+    pub mode: TransferMode,
+    pub paths: Vec<PathBuf>,
 }
 
 #[derive(Clone)]
@@ -32,6 +110,10 @@ impl<T> StatefulList<T> {
     }
 
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -46,6 +128,10 @@ impl<T> StatefulList<T> {
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -64,6 +150,8 @@ impl<T> StatefulList<T> {
     }
 }
 
+// only ever one instance, boxing the big variant buys nothing
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone)]
 pub enum AppState {
     Init,
@@ -73,31 +161,54 @@ pub enum AppState {
         last_index: HashMap<String, usize>,
         display_log: bool,
         display_help: bool,
+        /// First help line shown, the help is longer than the smallest screen
+        help_scroll: u16,
+        display_jobs: bool,
+        /// Show mode, owner, size and date next to each name
+        long_listing: bool,
+        /// Show the stat panel of the selected entry beside the list
+        display_properties: bool,
+        clipboard: Option<Clipboard>,
+        dialog: Option<Dialog>,
+        trash_view: Option<StatefulList<TrashEntry>>,
+        /// Marked entries from any directory, kept while navigating
+        marked: BTreeSet<PathBuf>,
+        /// Last entry toggled, where a range mark starts from
+        mark_anchor: Option<PathBuf>,
+        /// Archives entered, innermost last, while the cursor is inside one
+        archives: Vec<ArchiveMount>,
+        /// Where listings are read from
+        vfs: Arc<dyn Vfs>,
+        /// The list was read again since `take_relisted` was last called
+        relisted: bool,
     },
 }
 
 impl AppState {
     pub fn initialized() -> Self {
-        let mut items:Vec<File> = Vec::new();
-
-        let paths = std::fs::read_dir("./").unwrap();
-        for path in paths {
-            let entry = path.unwrap();
+        let cursor = std::env::current_dir().unwrap();
+        Self::with_vfs(Arc::new(LocalFs), cursor).unwrap()
+    }
 
-            items.push(File {
-                name: entry.file_name(),
-                path: entry.path(),
-                file_type: entry.file_type().unwrap(),
-                is_dir: entry.path().is_dir(),
-                metadata: entry.metadata().unwrap(),
-            });
-        }
+    /// Start browsing `cursor`, an absolute path, read through `vfs`
+    pub fn with_vfs(vfs: Arc<dyn Vfs>, cursor: PathBuf) -> io::Result<Self> {
+        // read through the absolute cursor so every path in the list is absolute
+        let items = read_entries(vfs.as_ref(), &cursor)?;
 
         let current_list = StatefulList::new(items);
-        let cursor = std::env::current_dir().unwrap();
         let mut last_index = HashMap::new();
         let display_log = false;
         let display_help = false;
+        let help_scroll = 0;
+        let display_jobs = false;
+        let long_listing = false;
+        let display_properties = false;
+        let clipboard = None;
+        let dialog = None;
+        let trash_view = None;
+        let marked = BTreeSet::new();
+        let mark_anchor = None;
+        let archives = Vec::new();
 
         last_index.insert(
             cursor.to_str().unwrap().to_string(),
@@ -110,11 +221,23 @@ impl AppState {
             last_index,
             display_log,
             display_help,
+            help_scroll,
+            display_jobs,
+            long_listing,
+            display_properties,
+            clipboard,
+            dialog,
+            trash_view,
+            marked,
+            mark_anchor,
+            archives,
+            vfs,
+            relisted: true,
         };
 
         ret.sort_dir_items();
 
-        ret
+        Ok(ret)
     }
 
     pub fn is_initialized(&self) -> bool {
@@ -122,11 +245,28 @@ impl AppState {
     }
 
     pub fn read_parent(&mut self) {
+        // the top of an archive leads back to the archive file itself
+        if let Initialized { cursor, archives, .. } = self {
+            if let Some(mount) = archives.last().filter(|mount| &mount.root == cursor) {
+                let archive = mount.archive.clone();
+                if let Err(err) = self.reveal(&archive) {
+                    error!("Cannot go back to {}: {}", archive.display(), err);
+                }
+                return;
+            }
+        }
+
         match self {
-            Initialized { cursor, last_index, current_list, .. } => {
+            Initialized { cursor, last_index, current_list, vfs, .. } => {
                 match cursor.parent() {
                     Some(path) => {
-                        let mut dir = path.read_dir().unwrap();
+                        let items = match read_entries(vfs.as_ref(), path) {
+                            Ok(items) => items,
+                            Err(err) => {
+                                error!("Cannot read {}: {}", path.display(), err);
+                                return;
+                            }
+                        };
                         let ori_dir_path = cursor.clone();
 
                         // record which item user left the cursor before transverse
@@ -136,7 +276,7 @@ impl AppState {
                         );
 
                         *cursor = path.to_path_buf();
-                        self.set_list(&mut dir);
+                        self.set_list(items);
                         self.adjust_parent_cursor(ori_dir_path.file_name().unwrap().to_str().unwrap());
                     }
 
@@ -168,13 +308,24 @@ impl AppState {
 
     pub fn read_dir(&mut self) {
         match self {
-            Initialized { cursor, current_list, last_index, .. } => {
-                let item:&File = current_list.items.get(current_list.index()).unwrap();
+            Initialized { cursor, current_list, last_index, vfs, .. } => {
+                let item:&File = match current_list.items.get(current_list.index()) {
+                    Some(item) => item,
+                    None => return,
+                };
 
                 if !item.is_dir {
                     return;
                 }
 
+                let items = match read_entries(vfs.as_ref(), &item.path) {
+                    Ok(items) => items,
+                    Err(err) => {
+                        error!("Cannot read {}: {}", item.path.display(), err);
+                        return;
+                    }
+                };
+
                 // record which item user left the cursor before transverse
                 last_index.insert(
                     cursor.to_str().unwrap().to_string(),
@@ -184,9 +335,7 @@ impl AppState {
                 let selected_dir = &item.name;
                 cursor.push(selected_dir.to_str().unwrap());
 
-                let mut selected_dir_path = std::fs::read_dir(cursor.to_str().unwrap()).unwrap();
-
-                self.set_list(&mut selected_dir_path);
+                self.set_list(items);
 
                 self.adjust_dir_cursor();
             },
@@ -194,6 +343,29 @@ impl AppState {
         }
     }
 
+    /// Browse the contents of `archive`, extracted to `root`, like a directory
+    pub fn enter_archive(&mut self, archive: &Path, root: PathBuf) {
+        let items = match self.read_entries(&root) {
+            Ok(items) => items,
+            Err(err) => {
+                error!("Cannot open {}: {}", archive.display(), err);
+                return;
+            }
+        };
+
+        if let Initialized { cursor, current_list, last_index, archives, .. } = self {
+            // record which item user left the cursor before transverse
+            last_index.insert(cursor.to_string_lossy().to_string(), current_list.index());
+
+            archives.push(ArchiveMount { archive: archive.to_path_buf(), root: root.clone() });
+            *cursor = root;
+        }
+
+        info!("Browsing {}", archive.display());
+        self.set_list(items);
+        self.adjust_dir_cursor();
+    }
+
     fn adjust_dir_cursor(&mut self) {
         match self {
             Initialized { cursor, current_list, last_index, .. } => {
@@ -205,8 +377,9 @@ impl AppState {
                         if *value >= current_list.items.len() {
                             current_list.state.select(Some(0));
                         }
-
-                        current_list.state.select(Some(*value));
+                        else {
+                            current_list.state.select(Some(*value));
+                        }
                     }
                 }
             },
@@ -214,28 +387,115 @@ impl AppState {
         }
     }
 
-    fn set_list(&mut self, dir:&mut ReadDir) {
-        if let Initialized { current_list, .. } = self {
-            let mut items:Vec<File> = Vec::new();
-
-            for path in dir {
-                let entry = path.unwrap();
-
-                items.push(File {
-                    name: entry.file_name(),
-                    path: entry.path(),
-                    file_type: entry.file_type().unwrap(),
-                    is_dir: entry.path().is_dir(),
-                    metadata: entry.metadata().unwrap(),
-                });
-            }
-
+    fn set_list(&mut self, items: Vec<File>) {
+        if let Initialized { current_list, relisted, .. } = self {
             current_list.items = items;
+            *relisted = true;
 
             self.sort_dir_items();
         }
     }
 
+    /// Entries of `dir` read through the state's filesystem
+    fn read_entries(&self, dir: &Path) -> io::Result<Vec<File>> {
+        match self {
+            Initialized { vfs, .. } => read_entries(vfs.as_ref(), dir),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Whether the list was read again since the last call
+    pub fn take_relisted(&mut self) -> bool {
+        if let Initialized { relisted, .. } = self {
+            std::mem::take(relisted)
+        }
+        else {
+            false
+        }
+    }
+
+    pub fn vfs(&self) -> Option<&dyn Vfs> {
+        if let Initialized { vfs, .. } = self {
+            Some(vfs.as_ref())
+        }
+        else {
+            None
+        }
+    }
+
+    /// The `Vfs` itself, for work that outlives this borrow like jobs
+    pub fn shared_vfs(&self) -> Option<Arc<dyn Vfs>> {
+        if let Initialized { vfs, .. } = self {
+            Some(vfs.clone())
+        }
+        else {
+            None
+        }
+    }
+
+    /// Browsing the local disk, where actions working on real paths are allowed
+    pub fn is_local(&self) -> bool {
+        self.vfs().is_some_and(|vfs| vfs.is_local())
+    }
+
+    /// Re-read the cursor directory, keeping the selection on the same name if it still exists
+    pub fn refresh(&mut self) {
+        if let Initialized { cursor, current_list, vfs, .. } = self {
+            let selected_name = current_list.items.get(current_list.index())
+                .map(|item| item.name.clone());
+
+            let items = match read_entries(vfs.as_ref(), cursor) {
+                Ok(items) => items,
+                Err(_) => return,
+            };
+
+            self.set_list(items);
+
+            if let Initialized { current_list, .. } = self {
+                let index = selected_name
+                    .and_then(|name| current_list.items.iter().position(|item| item.name == name))
+                    .unwrap_or(0);
+
+                current_list.state.select(Some(index));
+            }
+        }
+    }
+
+    /// Move the cursor to the directory holding `path` and select it there
+    pub fn reveal(&mut self, path: &Path) -> io::Result<()> {
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => return Err(io::Error::other(format!("{} is not inside a directory", path.display()))),
+        };
+
+        let items = self.read_entries(parent)?;
+
+        if let Initialized { cursor, current_list, last_index, archives, .. } = self {
+            // record which item user left the cursor before transverse
+            if let Some(index) = current_list.state.selected() {
+                last_index.insert(cursor.to_string_lossy().to_string(), index);
+            }
+
+            // leaving an archive, by its top or by a jump elsewhere
+            archives.retain(|mount| parent.starts_with(&mount.root));
+            *cursor = parent.to_path_buf();
+        }
+
+        self.set_list(items);
+        self.select_name(name);
+
+        Ok(())
+    }
+
+    /// Select the entry called `name` in the current listing, if there is one
+    pub fn select_name(&mut self, name: &OsStr) {
+        if let Initialized { current_list, .. } = self {
+            if let Some(index) = current_list.items.iter().position(|item| item.name == name) {
+                current_list.state.select(Some(index));
+            }
+        }
+    }
+
     pub fn sort_dir_items(&mut self) {
         if let Initialized { current_list, .. } = self {
             let mut dir_items:Vec<File>= Vec::new();
@@ -265,6 +525,30 @@ impl AppState {
         }
     }
 
+    /// The cursor as the user sees it, with archive contents shown under the archive's path
+    pub fn display_cursor(&self) -> Option<PathBuf> {
+        if let Initialized { cursor, archives, .. } = self {
+            let mut path = cursor.clone();
+            for mount in archives.iter().rev() {
+                match path.strip_prefix(&mount.root) {
+                    Ok(inner) if inner.as_os_str().is_empty() => path = mount.archive.clone(),
+                    Ok(inner) => path = mount.archive.join(inner),
+                    Err(_) => {}
+                }
+            }
+
+            Some(path)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Whether the cursor is inside an archive, whose contents are read-only
+    pub fn in_archive(&self) -> bool {
+        matches!(self, Initialized { archives, .. } if !archives.is_empty())
+    }
+
     pub fn current_list(&self) -> Option<&StatefulList<File>> {
         if let Initialized { current_list, .. } = self {
             Some(current_list)
@@ -274,6 +558,209 @@ impl AppState {
         }
     }
 
+    pub fn selected(&self) -> Option<&File> {
+        if let Initialized { current_list, .. } = self {
+            current_list.items.get(current_list.index())
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn marked(&self) -> Option<&BTreeSet<PathBuf>> {
+        if let Initialized { marked, .. } = self {
+            Some(marked)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Marked paths, or the selected entry when nothing is marked
+    pub fn targets(&self) -> Vec<PathBuf> {
+        match self.marked() {
+            Some(marked) if !marked.is_empty() => marked.iter().cloned().collect(),
+            _ => self.selected().map(|item| vec![item.path.clone()]).unwrap_or_default(),
+        }
+    }
+
+    /// Like `targets`, but clears the marks since the caller acts on them
+    pub fn take_targets(&mut self) -> Vec<PathBuf> {
+        let targets = self.targets();
+        self.unmark_all();
+        targets
+    }
+
+    /// Toggle the mark on the selected entry and move on to the next one
+    pub fn toggle_mark(&mut self) {
+        if let Initialized { current_list, marked, mark_anchor, .. } = self {
+            if let Some(item) = current_list.items.get(current_list.index()) {
+                if !marked.remove(&item.path) {
+                    marked.insert(item.path.clone());
+                }
+
+                *mark_anchor = Some(item.path.clone());
+                current_list.next();
+            }
+        }
+    }
+
+    /// Mark everything between the last toggled entry and the selected one
+    pub fn mark_range(&mut self) {
+        if let Initialized { current_list, marked, mark_anchor, .. } = self {
+            let index = current_list.index();
+            let anchor = mark_anchor.as_ref()
+                .and_then(|anchor| current_list.items.iter().position(|item| &item.path == anchor))
+                .unwrap_or(index);
+
+            for item in current_list.items.iter().take(anchor.max(index) + 1).skip(anchor.min(index)) {
+                marked.insert(item.path.clone());
+            }
+
+            if let Some(item) = current_list.items.get(index) {
+                *mark_anchor = Some(item.path.clone());
+            }
+        }
+    }
+
+    /// Mark every entry in the current listing
+    pub fn mark_all(&mut self) {
+        if let Initialized { current_list, marked, .. } = self {
+            marked.extend(current_list.items.iter().map(|item| item.path.clone()));
+        }
+    }
+
+    /// Drop every mark, including those in other directories
+    pub fn unmark_all(&mut self) {
+        if let Initialized { marked, mark_anchor, .. } = self {
+            marked.clear();
+            *mark_anchor = None;
+        }
+    }
+
+    pub fn set_marks(&mut self, paths: Vec<PathBuf>, mark: bool) {
+        if let Initialized { marked, .. } = self {
+            for path in paths {
+                if mark {
+                    marked.insert(path);
+                }
+                else {
+                    marked.remove(&path);
+                }
+            }
+        }
+    }
+
+    /// Flip the mark of every entry in the current listing
+    pub fn invert_marks(&mut self) {
+        if let Initialized { current_list, marked, .. } = self {
+            for item in &current_list.items {
+                if !marked.remove(&item.path) {
+                    marked.insert(item.path.clone());
+                }
+            }
+        }
+    }
+
+    pub fn clipboard(&self) -> Option<&Clipboard> {
+        if let Initialized { clipboard, .. } = self {
+            clipboard.as_ref()
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn set_clipboard(&mut self, mode: TransferMode) {
+        let paths = self.take_targets();
+
+        if let Initialized { clipboard, .. } = self {
+            if !paths.is_empty() {
+                *clipboard = Some(Clipboard { mode, paths });
+            }
+        }
+    }
+
+    pub fn take_clipboard(&mut self) -> Option<Clipboard> {
+        if let Initialized { clipboard, .. } = self {
+            clipboard.take()
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn dialog(&self) -> Option<&Dialog> {
+        if let Initialized { dialog, .. } = self {
+            dialog.as_ref()
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn dialog_mut(&mut self) -> Option<&mut Dialog> {
+        if let Initialized { dialog, .. } = self {
+            dialog.as_mut()
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn open_dialog(&mut self, new_dialog: Dialog) {
+        if let Initialized { dialog, .. } = self {
+            *dialog = Some(new_dialog);
+        }
+    }
+
+    pub fn close_dialog(&mut self) -> Option<Dialog> {
+        if let Initialized { dialog, .. } = self {
+            dialog.take()
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn trash_view(&self) -> Option<&StatefulList<TrashEntry>> {
+        if let Initialized { trash_view, .. } = self {
+            trash_view.as_ref()
+        }
+        else {
+            None
+        }
+    }
+
+    /// Show the trash instead of the cursor directory, or re-read it if already shown
+    pub fn open_trash_view(&mut self) -> std::io::Result<()> {
+        if let Initialized { trash_view, vfs, .. } = self {
+            let index = trash_view.as_ref().map_or(0, |list| list.index());
+            let mut list = StatefulList::new(trash::list(vfs.as_ref())?);
+
+            if index < list.items.len() {
+                list.state.select(Some(index));
+            }
+            else {
+                list.state.select(Some(list.items.len().saturating_sub(1)));
+            }
+
+            *trash_view = Some(list);
+        }
+
+        Ok(())
+    }
+
+    pub fn close_trash_view(&mut self) {
+        if let Initialized { trash_view, .. } = self {
+            *trash_view = None;
+        }
+    }
+
+    pub fn selected_trash_entry(&self) -> Option<&TrashEntry> {
+        self.trash_view().and_then(|list| list.items.get(list.index()))
+    }
+
     pub fn display_log(&self) -> Option<&bool> {
         if let Initialized { display_log, .. } = self {
             Some(display_log)
@@ -289,6 +776,36 @@ impl AppState {
         }
     }
 
+    pub fn long_listing(&self) -> Option<&bool> {
+        if let Initialized { long_listing, .. } = self {
+            Some(long_listing)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn toggle_long_listing(&mut self) {
+        if let Initialized { long_listing, .. } = self {
+            *long_listing = !*long_listing;
+        }
+    }
+
+    pub fn display_properties(&self) -> Option<&bool> {
+        if let Initialized { display_properties, .. } = self {
+            Some(display_properties)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn toggle_properties(&mut self) {
+        if let Initialized { display_properties, .. } = self {
+            *display_properties = !*display_properties;
+        }
+    }
+
     pub fn display_help(&self) -> Option<&bool> {
         if let Initialized { display_help, .. } = self {
             Some(display_help)
@@ -299,8 +816,39 @@ impl AppState {
     }
 
     pub fn toggle_help(&mut self) {
-        if let Initialized { display_help, .. } = self {
+        if let Initialized { display_help, help_scroll, .. } = self {
             *display_help = !*display_help;
+            *help_scroll = 0;
+        }
+    }
+
+    pub fn help_scroll(&self) -> u16 {
+        if let Initialized { help_scroll, .. } = self {
+            *help_scroll
+        }
+        else {
+            0
+        }
+    }
+
+    pub fn set_help_scroll(&mut self, scroll: u16) {
+        if let Initialized { help_scroll, .. } = self {
+            *help_scroll = scroll;
+        }
+    }
+
+    pub fn display_jobs(&self) -> Option<&bool> {
+        if let Initialized { display_jobs, .. } = self {
+            Some(display_jobs)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn toggle_jobs(&mut self) {
+        if let Initialized { display_jobs, .. } = self {
+            *display_jobs = !*display_jobs;
         }
     }
 }
@@ -0,0 +1,224 @@
+//! Free-form tags on files.
+//!
+//! Tags live in the `user.file_tui.tags` xattr so they follow the file around.
+//! On filesystems without xattr support they go to a sidecar database under
+//! the XDG data directory instead, keyed by path.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::thread::JoinHandle;
+
+use log::warn;
+
+use crate::app::config::data_home;
+use crate::app::trash::{decode_path, encode_path};
+use crate::app::vfs::{LocalFs, Vfs};
+use crate::app::xattrs;
+
+pub const TAGS_XATTR: &str = "user.file_tui.tags";
+
+/// No such attribute, Linux value
+const ENODATA: i32 = 61;
+
+/// Tags kept outside the files, for filesystems that can't hold them
+#[derive(Default)]
+pub struct TagStore {
+    sidecar: BTreeMap<PathBuf, Vec<String>>,
+    /// Sidecar database, `None` keeps it in memory only
+    path: Option<PathBuf>,
+}
+
+impl TagStore {
+    pub fn load() -> Self {
+        let path = data_home().join("file_tui").join("tags");
+        let mut sidecar = BTreeMap::new();
+
+        for line in fs::read_to_string(&path).unwrap_or_default().lines() {
+            match line.split_once('\t') {
+                Some((file, tags)) => {
+                    sidecar.insert(decode_path(file), parse(tags));
+                }
+                None => warn!("Tags: ignoring malformed line '{}'", line),
+            }
+        }
+
+        Self { sidecar, path: Some(path) }
+    }
+
+    /// Tags of `path`, from its xattr or else from the sidecar
+    pub fn tags(&self, path: &Path) -> Vec<String> {
+        match read_xattr(&LocalFs, path) {
+            Some(tags) => tags,
+            None => self.sidecar.get(path).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Sidecar tags only, for entries whose xattr tags were read with the listing
+    pub fn sidecar_tags(&self, path: &Path) -> &[String] {
+        self.sidecar.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn set_tags(&mut self, path: &Path, tags: &[String]) -> io::Result<()> {
+        let result = if tags.is_empty() {
+            match xattr::remove(path, TAGS_XATTR) {
+                // there was nothing to remove
+                Err(err) if err.raw_os_error() == Some(ENODATA) => Ok(()),
+                result => result,
+            }
+        }
+        else {
+            xattr::set(path, TAGS_XATTR, tags.join(",").as_bytes())
+        };
+
+        match result {
+            Ok(_) => {
+                // the xattr wins from now on, drop any stale sidecar entry
+                if self.sidecar.remove(path).is_some() {
+                    self.save();
+                }
+                Ok(())
+            }
+            Err(err) if xattrs::is_unsupported(&err) => {
+                if tags.is_empty() {
+                    self.sidecar.remove(path);
+                }
+                else {
+                    self.sidecar.insert(path.to_path_buf(), tags.to_vec());
+                }
+                self.save();
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Sidecar entries carrying `tag`
+    pub fn sidecar_paths(&self, tag: &str) -> Vec<PathBuf> {
+        self.sidecar.iter()
+            .filter(|(_, tags)| tags.iter().any(|t| t == tag))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut content = String::new();
+        for (path, tags) in &self.sidecar {
+            content.push_str(&format!("{}\t{}\n", encode_path(path), tags.join(",")));
+        }
+
+        let result = path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, content));
+
+        if let Err(err) = result {
+            warn!("Cannot save tags to {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// Tags from the xattr of `path`, `None` when it has no tag xattr
+pub fn read_xattr(vfs: &dyn Vfs, path: &Path) -> Option<Vec<String>> {
+    let value = vfs.xattr(path, TAGS_XATTR).ok()??;
+    Some(parse(&String::from_utf8_lossy(&value)))
+}
+
+/// Comma or space separated tags, without duplicates
+pub fn parse(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+
+    for tag in text.split([',', ' ']).map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+
+    tags
+}
+
+/// Apply what was typed in the tag prompt to `tags`.
+///
+/// When every word starts with `+` or `-` the words add and remove tags,
+/// otherwise they replace the whole set.
+pub fn edit(tags: &[String], text: &str) -> Vec<String> {
+    let words = parse(text);
+
+    if !words.iter().all(|word| word.starts_with('+') || word.starts_with('-')) {
+        return words;
+    }
+
+    let mut tags = tags.to_vec();
+    for word in words {
+        let (op, tag) = word.split_at(1);
+        if tag.is_empty() {
+            continue;
+        }
+
+        tags.retain(|t| t != tag);
+        if op == "+" {
+            tags.push(tag.to_string());
+        }
+    }
+
+    tags
+}
+
+/// `find` running on its own thread so a big tree doesn't freeze the ui
+pub struct TagSearch {
+    pub tag: String,
+    handle: JoinHandle<Vec<PathBuf>>,
+}
+
+impl TagSearch {
+    pub fn start(roots: Vec<PathBuf>, tag: String) -> Self {
+        let search_tag = tag.clone();
+        let handle = thread::spawn(move || find(&roots, &search_tag));
+
+        Self { tag, handle }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    pub fn join(self) -> Vec<PathBuf> {
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+/// Files under `roots` whose tag xattr holds `tag`, symlinks are not followed
+pub fn find(roots: &[PathBuf], tag: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+
+    for root in roots {
+        find_in(root, tag, &mut found);
+    }
+
+    found
+}
+
+fn find_in(dir: &Path, tag: &str, found: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if read_xattr(&LocalFs, &path).is_some_and(|tags| tags.iter().any(|t| t == tag)) {
+            found.push(path.clone());
+        }
+
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            find_in(&path, tag, found);
+        }
+    }
+}
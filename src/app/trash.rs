@@ -0,0 +1,275 @@
+//! Home trash following the freedesktop.org trash specification.
+//!
+//! Trashed entries live in `$XDG_DATA_HOME/Trash/files` with a matching
+//! `info/<name>.trashinfo` recording the original path and deletion date.
+//! Per-volume `$topdir/.Trash` directories are not used, entries on other
+//! filesystems are copied into the home trash instead. The trash lives on the
+//! same `Vfs` as the entries thrown in it.
+
+use std::cmp::Reverse;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, NaiveDateTime, SubsecRound};
+
+use crate::app::config::data_home;
+use crate::app::jobs::{copy_path, JobProgress, EXDEV};
+use crate::app::vfs;
+use crate::app::vfs::Vfs;
+
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    /// File name inside `files/`, also the stem of the `.trashinfo`
+    pub name: OsString,
+    pub original_path: PathBuf,
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Total bytes of the trashed file or tree
+    pub size: u64,
+}
+
+impl TrashEntry {
+    pub fn path(&self) -> PathBuf {
+        files_dir().join(&self.name)
+    }
+
+    fn info_path(&self) -> PathBuf {
+        info_path(&self.name)
+    }
+}
+
+pub fn trash_dir() -> PathBuf {
+    data_home().join("Trash")
+}
+
+fn files_dir() -> PathBuf {
+    trash_dir().join("files")
+}
+
+fn info_dir() -> PathBuf {
+    trash_dir().join("info")
+}
+
+fn info_path(name: &OsStr) -> PathBuf {
+    let mut file_name = name.to_os_string();
+    file_name.push(".trashinfo");
+    info_dir().join(file_name)
+}
+
+/// Move `path` into the trash, returning the entry that can restore it
+pub fn trash(vfs: &dyn Vfs, path: &Path) -> io::Result<TrashEntry> {
+    let original_path = std::path::absolute(path)?;
+    vfs::create_dir_all(vfs, &files_dir())?;
+    vfs::create_dir_all(vfs, &info_dir())?;
+
+    let deleted_at = Local::now().naive_local().trunc_subsecs(0);
+    let size = tree_size(vfs, path);
+    let name = reserve_info(vfs, original_path.file_name().unwrap_or_default())?;
+
+    let content = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(&original_path),
+        deleted_at.format(DATE_FORMAT),
+    );
+
+    let entry = TrashEntry {
+        name,
+        original_path,
+        deleted_at: Some(deleted_at),
+        size,
+    };
+
+    let result = vfs.write(&entry.info_path(), content.as_bytes())
+        .and_then(|_| move_entry(vfs, path, &entry.path()));
+
+    if let Err(err) = result {
+        let _ = vfs.remove(&entry.info_path());
+        return Err(err);
+    }
+
+    Ok(entry)
+}
+
+/// Move a trashed entry back to where it came from
+pub fn restore(vfs: &dyn Vfs, entry: &TrashEntry) -> io::Result<()> {
+    restore_to(vfs, entry, &entry.original_path)
+}
+
+/// Move a trashed entry out of the trash to `target`
+pub fn restore_to(vfs: &dyn Vfs, entry: &TrashEntry, target: &Path) -> io::Result<()> {
+    if vfs.symlink_stat(target).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", target.display()),
+        ));
+    }
+
+    if let Some(parent) = target.parent() {
+        // the original directory may have been removed since
+        vfs::create_dir_all(vfs, parent)?;
+    }
+
+    move_entry(vfs, &entry.path(), target)?;
+    vfs.remove(&entry.info_path())
+}
+
+/// Remove every trashed entry for good
+pub fn empty(vfs: &dyn Vfs) -> io::Result<()> {
+    for entry in list(vfs)? {
+        purge(vfs, &entry)?;
+    }
+
+    Ok(())
+}
+
+/// Remove a trashed entry for good
+pub fn purge(vfs: &dyn Vfs, entry: &TrashEntry) -> io::Result<()> {
+    let path = entry.path();
+    if vfs.symlink_stat(&path).is_ok() {
+        vfs.remove(&path)?;
+    }
+
+    vfs.remove(&entry.info_path())
+}
+
+/// Every entry with a readable `.trashinfo`, newest first
+pub fn list(vfs: &dyn Vfs) -> io::Result<Vec<TrashEntry>> {
+    let mut entries = Vec::new();
+
+    let dir = match vfs.list(&info_dir()) {
+        Ok(dir) => dir,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(entries),
+        Err(err) => return Err(err),
+    };
+
+    for info in dir {
+        if info.extension() != Some(OsStr::new("trashinfo")) {
+            continue;
+        }
+
+        if let Some(entry) = read_info(vfs, &info) {
+            entries.push(entry);
+        }
+    }
+
+    entries.sort_by_key(|entry| Reverse(entry.deleted_at));
+
+    Ok(entries)
+}
+
+fn read_info(vfs: &dyn Vfs, info: &Path) -> Option<TrashEntry> {
+    let content = String::from_utf8(vfs.read(info).ok()?).ok()?;
+
+    let mut original_path = None;
+    let mut deleted_at = None;
+    for line in content.lines() {
+        if let Some(path) = line.strip_prefix("Path=") {
+            original_path = Some(decode_path(path));
+        }
+        else if let Some(date) = line.strip_prefix("DeletionDate=") {
+            deleted_at = NaiveDateTime::parse_from_str(date, DATE_FORMAT).ok();
+        }
+    }
+
+    let name = info.file_stem()?.to_os_string();
+    let size = tree_size(vfs, &files_dir().join(&name));
+
+    Some(TrashEntry {
+        name,
+        original_path: original_path?,
+        deleted_at,
+        size,
+    })
+}
+
+fn tree_size(vfs: &dyn Vfs, path: &Path) -> u64 {
+    let stat = match vfs.symlink_stat(path) {
+        Ok(stat) => stat,
+        Err(_) => return 0,
+    };
+
+    if !stat.is_dir() {
+        return stat.size;
+    }
+
+    vfs.list(path)
+        .map(|entries| entries.iter().map(|entry| tree_size(vfs, entry)).sum())
+        .unwrap_or(0)
+}
+
+/// Create the `.trashinfo` with O_EXCL under the first free name, which claims that name
+fn reserve_info(vfs: &dyn Vfs, file_name: &OsStr) -> io::Result<OsString> {
+    let mut n = 1;
+
+    loop {
+        let mut name = file_name.to_os_string();
+        if n > 1 {
+            name.push(format!(".{}", n));
+        }
+
+        if vfs.symlink_stat(&files_dir().join(&name)).is_err() {
+            match vfs.create_file(&info_path(&name)) {
+                Ok(_) => return Ok(name),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        n += 1;
+    }
+}
+
+fn move_entry(vfs: &dyn Vfs, from: &Path, to: &Path) -> io::Result<()> {
+    match vfs.rename(from, to) {
+        Err(err) if err.raw_os_error() == Some(EXDEV) => {
+            copy_path(vfs, from, to, &JobProgress::default())?;
+            vfs.remove(from)
+        }
+        result => result,
+    }
+}
+
+/// Percent-encode everything but unreserved characters and `/`, as the spec asks
+pub(crate) fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+
+    for byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(*byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+pub(crate) fn decode_path(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes.get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    PathBuf::from(OsString::from_vec(decoded))
+}
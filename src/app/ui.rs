@@ -1,18 +1,33 @@
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+
 use tui::backend::Backend;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Modifier, Style};
-use tui::widgets::{Block, BorderType, Borders, Paragraph, List, ListItem, Wrap};
+use tui::widgets::{Block, BorderType, Borders, Clear, Paragraph, List, ListItem, ListState, Wrap};
 use tui::Frame;
-use tui::text::Text;
+use tui::text::{Span, Spans, Text};
 use tui_logger::TuiLoggerWidget;
 
 extern crate chrono;
 use chrono::offset::Utc;
 use chrono::DateTime;
-use log::debug;
 
 use crate::app::App;
-use crate::app::state::AppState;
+use crate::app::actions::Action;
+use crate::app::batch_rename;
+use crate::app::dialog::{
+    BatchRenameDialog, BatchRenameField, ChmodDialog, ChmodField, Dialog, InputPurpose, PathListDialog, TextInput,
+    XattrDialog,
+};
+use crate::app::jobs::Job;
+use crate::app::owners::Owners;
+use crate::app::permissions::format_mode;
+use crate::app::state::{AppState, File};
+use crate::app::trash;
+use crate::app::vfs::{EntryKind, Vfs};
+use crate::app::xattrs;
 
 pub fn draw<B>(rect: &mut Frame<B>, _app: &mut App)
     where
@@ -46,13 +61,19 @@ pub fn draw<B>(rect: &mut Frame<B>, _app: &mut App)
             .constraints([Constraint::Min(3), ].as_ref())
             .split(size);
 
+        // scrolling stops once the last line is on screen
+        let lines = help_lines(_app.actions.actions(), chunks[0].width.saturating_sub(2) as usize);
+        let max_scroll = (lines.len() as u16).saturating_sub(chunks[0].height.saturating_sub(2));
+        let scroll = _app.state.help_scroll().min(max_scroll);
+        _app.state.set_help_scroll(scroll);
 
-
-        rect.render_widget(draw_help(), chunks[0]);
+        rect.render_widget(draw_help(lines, scroll, max_scroll > 0), chunks[0]);
         return;
     }
 
     let display_log = *_app.state.display_log().unwrap();
+    let display_jobs = *_app.state.display_jobs().unwrap();
+    let jobs_height = 2 + 2 * _app.jobs.jobs().len().clamp(1, 4) as u16;
 
     // Vertical layout
     let chunks = Layout::default()
@@ -62,25 +83,107 @@ pub fn draw<B>(rect: &mut Frame<B>, _app: &mut App)
                 Constraint::Length(3),
                 Constraint::Min(10),
                 Constraint::Length(1),
+                if display_jobs { Constraint::Length(jobs_height) } else { Constraint::Length(0) },
                 if display_log { Constraint::Length(10) } else { Constraint::Length(0) },
             ].as_ref())
         .split(size);
 
+    if _app.state.trash_view().is_some() {
+        draw_trash_view(rect, _app, &chunks);
+    }
+    else {
+        draw_browser(rect, _app, &chunks);
+    }
+
+    if display_jobs {
+        let (jobs, mut jobs_state) = draw_jobs(_app.jobs.jobs(), _app.jobs.selected());
+        rect.render_stateful_widget(jobs, chunks[3], &mut jobs_state);
+    }
+
+    if display_log {
+        let logs = draw_logs();
+        rect.render_widget(logs, chunks[4]);
+    }
+
+    if let (Some(dialog), Some(vfs)) = (_app.state.dialog(), _app.state.vfs()) {
+        draw_dialog(rect, dialog, vfs);
+    }
+}
+
+fn draw_browser<B>(rect: &mut Frame<B>, _app: &mut App, chunks: &[Rect])
+    where
+        B: Backend,
+{
     // Title
-    let str = _app.state.cursor().unwrap().to_str().unwrap();
-    let title = draw_title(str);
+    let mut str = _app.state.display_cursor().unwrap().to_string_lossy().to_string();
+    if _app.state.in_archive() {
+        str.push_str("  [read-only]");
+    }
+    let title = draw_title(&str);
     rect.render_widget(title, chunks[0]);
 
-    if let AppState::Initialized { current_list, .. } = &mut _app.state {
+    let display_properties = *_app.state.display_properties().unwrap();
+    let list_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Min(20),
+                if display_properties { Constraint::Percentage(40) } else { Constraint::Length(0) },
+            ].as_ref())
+        .split(chunks[1]);
+
+    let owners = &_app.owners;
+    let tags = &_app.tags;
+    let notes = &_app.notes;
+    if let AppState::Initialized { current_list, marked, long_listing, .. } = &mut _app.state {
         let mut list_items:Vec<ListItem> = Vec::new();
 
         for item in &current_list.items {
-            list_items.push(
-                ListItem::new(item.name.to_str().unwrap())
-                    .style(
-                        Style::default().fg(if item.is_dir {Color::Green} else {Color::White} )
-                    )
-            );
+            let is_marked = marked.contains(&item.path);
+            let color = if is_marked {
+                Color::Yellow
+            }
+            else if item.is_broken_link() {
+                Color::Red
+            }
+            else if item.link.is_some() {
+                Color::Cyan
+            }
+            else if item.is_dir {
+                Color::Green
+            }
+            else {
+                Color::White
+            };
+
+            let mut text = String::from(if is_marked { "* " } else { "  " });
+            if *long_listing {
+                text.push_str(&format!(
+                    "{}{} {:8} {:8} {:>9}  {}  ",
+                    type_char(item),
+                    format_mode(item.metadata.mode),
+                    owners.user(item.metadata.uid),
+                    owners.group(item.metadata.gid),
+                    human_size(item.metadata.size),
+                    item.metadata.modified.map(format_time).unwrap_or_default(),
+                ));
+            }
+            text.push_str(&item.name.to_string_lossy());
+            if let Some(link) = &item.link {
+                text.push_str(&format!(" -> {}", link.target.display()));
+            }
+            else if !item.is_dir && item.metadata.nlink > 1 {
+                text.push_str(&format!("  [{} links]", item.metadata.nlink));
+            }
+
+            let mut spans = vec![Span::raw(text)];
+            let item_tags = if item.tags.is_empty() { tags.sidecar_tags(&item.path) } else { &item.tags };
+            spans.extend(tag_chips(item_tags));
+            if notes.note(&item.path, &item.metadata).is_some() {
+                spans.push(Span::styled("  [note]", Style::default().fg(Color::LightYellow)));
+            }
+
+            list_items.push(ListItem::new(Spans::from(spans)).style(Style::default().fg(color)));
         }
 
         if current_list.state.selected().unwrap() >= current_list.items.len() {
@@ -88,32 +191,634 @@ pub fn draw<B>(rect: &mut Frame<B>, _app: &mut App)
         }
 
         let list = draw_list(list_items);
-        rect.render_stateful_widget(list, chunks[1], &mut current_list.state);
+        rect.render_stateful_widget(list, list_chunks[0], &mut current_list.state);
     }
 
-    let selected_item = _app.state.current_list().unwrap()
-        .items.get(_app.state.current_list().unwrap()
-        .index()).unwrap()
-        .clone();
-    let datetime:DateTime<Utc> = selected_item.metadata.modified().unwrap().into();
+    let note = _app.state.selected().and_then(|item| notes.note(&item.path, &item.metadata));
 
-    let datetime_str = format!("Modified: {}", datetime.format("%d/%m/%Y %T").to_string());
-    let right_align_str = "[?] Help, Hello World";
-    let whitespace_num = size.width as usize - datetime_str.len() - right_align_str.len();
+    if display_properties {
+        rect.render_widget(draw_properties(_app.state.selected(), owners, note), list_chunks[1]);
+    }
 
-    debug!("{}, {}, {}, {}", datetime_str.len(), right_align_str.len(), size.width, whitespace_num);
+    let mut status_str = match _app.state.selected() {
+        Some(selected_item) => format!(
+            "Modified: {}  Owner: {}:{}",
+            selected_item.metadata.modified.map(format_time).unwrap_or_default(),
+            owners.user(selected_item.metadata.uid),
+            owners.group(selected_item.metadata.gid),
+        ),
+        None => String::new(),
+    };
 
-    let detail_str = String::from(format!("{}{:num$}{}", datetime_str, " ", right_align_str, num = whitespace_num));
-    let detail = Paragraph::new(detail_str)
+    // the whole note is in the properties panel, the first line has to do otherwise
+    if let Some(line) = note.filter(|_| !display_properties).and_then(|note| note.lines().next()) {
+        status_str.push_str(&format!("  Note: {}", line));
+    }
+
+    let marked_count = _app.state.marked().map_or(0, |marked| marked.len());
+    if marked_count > 0 {
+        status_str.push_str(&format!("  Marked: {}", marked_count));
+    }
+
+    rect.render_widget(draw_detail(&status_str, chunks[2].width), chunks[2]);
+}
+
+fn draw_trash_view<B>(rect: &mut Frame<B>, _app: &mut App, chunks: &[Rect])
+    where
+        B: Backend,
+{
+    let trash_dir = trash::trash_dir();
+    rect.render_widget(draw_title(&trash_dir.to_string_lossy()), chunks[0]);
+
+    if let AppState::Initialized { trash_view: Some(trash_list), .. } = &mut _app.state {
+        let mut list_items:Vec<ListItem> = Vec::new();
+
+        for entry in &trash_list.items {
+            let deleted_at = entry.deleted_at
+                .map(|date| date.format("%d/%m/%Y %T").to_string())
+                .unwrap_or_else(|| String::from("unknown date"));
+
+            list_items.push(ListItem::new(format!(
+                "{:19}  {:>10}  {}",
+                deleted_at,
+                human_size(entry.size),
+                entry.original_path.display(),
+            )));
+        }
+
+        if list_items.is_empty() {
+            list_items.push(ListItem::new("Trash is empty"));
+        }
+
+        let list = draw_list(list_items);
+        rect.render_stateful_widget(list, chunks[1], &mut trash_list.state);
+    }
+
+    let count = _app.state.trash_view().map_or(0, |list| list.items.len());
+    let detail_str = format!("{} item(s) in trash", count);
+
+    rect.render_widget(draw_detail(&detail_str, chunks[2].width), chunks[2]);
+}
+
+/// Status line with `left_str` on the left and the help hint on the right
+fn draw_detail<'a>(left_str: &str, width: u16) -> Paragraph<'a> {
+    let right_align_str = "[?] Help, Hello World";
+    let whitespace_num = (width as usize).saturating_sub(left_str.len() + right_align_str.len());
+
+    let detail_str = format!("{}{:num$}{}", left_str, "", right_align_str, num = whitespace_num);
+    Paragraph::new(detail_str)
         .style(Style::default().fg(Color::LightCyan))
-        .alignment(Alignment::Left);
+        .alignment(Alignment::Left)
+}
 
-    rect.render_widget(detail, chunks[2]);
+fn draw_dialog<B>(rect: &mut Frame<B>, dialog: &Dialog, vfs: &dyn Vfs)
+    where
+        B: Backend,
+{
+    match dialog {
+        Dialog::Conflict(conflict) => {
+            let transfer = match conflict.current() {
+                Some(transfer) => transfer,
+                None => return,
+            };
 
-    if display_log {
-        let logs = draw_logs();
-        rect.render_widget(logs, chunks[3]);
+            let area = centered_rect(rect.size(), 90, 12);
+            let block = Block::default()
+                .title(format!("Target already exists ({} left)", conflict.pending.len()))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .style(Style::default().fg(Color::Yellow));
+
+            let inner = block.inner(area);
+            rect.render_widget(Clear, area);
+            rect.render_widget(block, area);
+
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(5), Constraint::Length(3)].as_ref())
+                .split(inner);
+
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(chunks[0]);
+
+            rect.render_widget(draw_candidate(vfs, "Source", &transfer.from), columns[0]);
+            rect.render_widget(draw_candidate(vfs, "Target", &transfer.to), columns[1]);
+
+            let options = format!(
+                "[o] Overwrite  [s] Skip  [r] Rename  [n] Overwrite if newer\n[a] Apply to all: {}  [Esc] Cancel paste",
+                if conflict.apply_all { "on" } else { "off" }
+            );
+            let options = Paragraph::new(options)
+                .style(Style::default().fg(Color::White))
+                .wrap(Wrap { trim: true });
+
+            rect.render_widget(options, chunks[1]);
+        }
+        Dialog::Confirm(confirm) => {
+            let height = confirm.message.lines().count() as u16 + 4;
+            let area = centered_rect(rect.size(), 60, height);
+            let paragraph = Paragraph::new(format!("{}\n[y] Yes  [any other key] No", confirm.message))
+                .style(Style::default().fg(Color::White))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+                .block(
+                    Block::default()
+                        .title("Confirm")
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .style(Style::default().fg(Color::Red)),
+                );
+
+            rect.render_widget(Clear, area);
+            rect.render_widget(paragraph, area);
+        }
+        Dialog::Input(input) => {
+            let hint = match &input.purpose {
+                InputPurpose::MarkPattern { regex, recursive, .. } => format!(
+                    "[Tab] {}  [Ctrl+r] Recursive: {}",
+                    if *regex { "Regex" } else { "Glob" },
+                    if *recursive { "on" } else { "off" },
+                ),
+                InputPurpose::Rename { .. } => String::from("[Enter] Rename  [Esc] Cancel"),
+                InputPurpose::New(_) => String::from("Nested paths like a/b/c create missing directories"),
+                InputPurpose::NewFromTemplate { templates, index } => format!(
+                    "[Tab] Template: {} ({}/{})",
+                    templates[*index].file_name().unwrap_or_default().to_string_lossy(),
+                    index + 1,
+                    templates.len(),
+                ),
+                InputPurpose::Chown { targets } => format!("user, user:group or :group for {} entr(ies)", targets.len()),
+                InputPurpose::SetXattr { name: None, .. } => String::from("name=value, a value starting with 0x is hex bytes"),
+                InputPurpose::SetXattr { .. } => String::from("A value starting with 0x is hex bytes"),
+                InputPurpose::Tags { targets } => format!(
+                    "Tags for {} entr(ies), +tag or -tag to add or remove only", targets.len()
+                ),
+                InputPurpose::TagView => String::from("Searches the tag roots set in the config"),
+                InputPurpose::NoteSearch => String::from("Finds notes containing the text, ignoring case"),
+                InputPurpose::Compress { sources } => format!(
+                    "{} entr(ies), .zip, .tar, .tar.gz or .tar.zst picks the format", sources.len()
+                ),
+                InputPurpose::ExtractTo { .. } => String::from("Created if missing, existing files are never overwritten"),
+            };
+
+            let mut lines = vec![draw_text_input(&input.input), Spans::from("")];
+            match &input.error {
+                Some(error) => lines.push(Spans::from(Span::styled(error.clone(), Style::default().fg(Color::Red)))),
+                None => lines.push(Spans::from(Span::styled(hint, Style::default().fg(Color::Gray)))),
+            }
+
+            let area = centered_rect(rect.size(), 70, 5);
+            let paragraph = Paragraph::new(lines)
+                .style(Style::default().fg(Color::White))
+                .block(
+                    Block::default()
+                        .title(input.title.clone())
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .style(Style::default().fg(Color::LightCyan)),
+                );
+
+            rect.render_widget(Clear, area);
+            rect.render_widget(paragraph, area);
+        }
+        Dialog::BatchRename(batch) => draw_batch_rename(rect, batch, vfs),
+        Dialog::Chmod(chmod) => draw_chmod(rect, chmod),
+        Dialog::Xattrs(xattrs) => draw_xattrs(rect, xattrs),
+        Dialog::PathList(path_list) => draw_path_list(rect, path_list),
+        Dialog::Message(message) => {
+            let lines: Vec<Spans> = message.lines.iter()
+                .map(|line| Spans::from(line.as_str()))
+                .chain([Spans::from(""), Spans::from(Span::styled("[any key] Close", Style::default().fg(Color::Gray)))])
+                .collect();
+
+            let area = centered_rect(rect.size(), 80, lines.len() as u16 + 2);
+            let paragraph = Paragraph::new(lines)
+                .style(Style::default().fg(Color::White))
+                .wrap(Wrap { trim: false })
+                .block(
+                    Block::default()
+                        .title(message.title.as_str())
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .style(Style::default().fg(Color::Red)),
+                );
+
+            rect.render_widget(Clear, area);
+            rect.render_widget(paragraph, area);
+        }
+    }
+}
+
+fn draw_batch_rename<B>(rect: &mut Frame<B>, dialog: &BatchRenameDialog, vfs: &dyn Vfs)
+    where
+        B: Backend,
+{
+    let size = rect.size();
+    let area = centered_rect(size, 90, size.height.saturating_sub(4));
+    let block = Block::default()
+        .title(format!("Batch rename {} entr(ies)", dialog.sources.len()))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::LightCyan));
+
+    let inner = block.inner(area);
+    rect.render_widget(Clear, area);
+    rect.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(6), Constraint::Min(3), Constraint::Length(1)].as_ref())
+        .split(inner);
+
+    let mut field_lines = Vec::new();
+    for field in BatchRenameField::all() {
+        let label_style = if *field == dialog.focus {
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        }
+        else {
+            Style::default().fg(Color::Gray)
+        };
+
+        let mut spans = vec![Span::styled(format!("{:10}", format!("{:?}:", field)), label_style)];
+        match dialog.input(*field) {
+            Some(input) if *field == dialog.focus => spans.extend(draw_text_input(input).0),
+            Some(input) => spans.push(Span::raw(input.text().to_string())),
+            None => spans.push(Span::raw(format!("< {} >", dialog.case))),
+        }
+
+        field_lines.push(Spans::from(spans));
+    }
+
+    rect.render_widget(Paragraph::new(field_lines).style(Style::default().fg(Color::White)), chunks[0]);
+
+    let mut preview_items: Vec<ListItem> = Vec::new();
+    match batch_rename::preview(vfs, &dialog.sources, &dialog.options()) {
+        Ok(rows) => {
+            for row in rows {
+                let from = row.from.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let to = row.to.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+                let item = match &row.problem {
+                    Some(problem) => ListItem::new(format!("{} -> {}  ({})", from, to, problem))
+                        .style(Style::default().fg(Color::Red)),
+                    None if row.is_unchanged() => ListItem::new(format!("{} (unchanged)", from))
+                        .style(Style::default().fg(Color::Gray)),
+                    None => ListItem::new(format!("{} -> {}", from, to))
+                        .style(Style::default().fg(Color::White)),
+                };
+                preview_items.push(item);
+            }
+        }
+        Err(err) => preview_items.push(ListItem::new(err).style(Style::default().fg(Color::Red))),
+    }
+
+    let preview = List::new(preview_items)
+        .block(
+            Block::default()
+                .title("Preview")
+                .borders(Borders::TOP),
+        );
+    rect.render_widget(preview, chunks[1]);
+
+    let hint = Paragraph::new("[Tab/Up/Down] Field  [Left/Right] Case  [Enter] Apply  [Esc] Cancel  {n} Counter")
+        .style(Style::default().fg(Color::Gray));
+    rect.render_widget(hint, chunks[2]);
+}
+
+fn draw_chmod<B>(rect: &mut Frame<B>, dialog: &ChmodDialog)
+    where
+        B: Backend,
+{
+    let title = match dialog.targets.as_slice() {
+        [target] => format!("Permissions of {}", target.file_name().unwrap_or_default().to_string_lossy()),
+        targets => format!("Permissions of {} entries", targets.len()),
+    };
+
+    let preview = dialog.preview();
+    let mode = *preview.as_ref().unwrap_or(&dialog.mode);
+    let focused = |field: ChmodField| if dialog.focus == field {
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+    }
+    else {
+        Style::default().fg(Color::Gray)
+    };
+
+    let mut lines = vec![Spans::from(Span::styled("         r   w   x", focused(ChmodField::Grid)))];
+    for (row, class) in ["User", "Group", "Other"].iter().enumerate() {
+        let mut spans = vec![Span::raw(format!("  {:6}", class))];
+
+        for column in 0..3 {
+            let set = mode & (0o400 >> (row * 3 + column)) != 0;
+            let style = if dialog.focus == ChmodField::Grid && dialog.grid_cursor == (row, column) {
+                Style::default().add_modifier(Modifier::REVERSED)
+            }
+            else {
+                Style::default()
+            };
+
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(if set { "[x]" } else { "[ ]" }, style));
+        }
+
+        lines.push(Spans::from(spans));
+    }
+
+    lines.push(Spans::from(""));
+    lines.push(match &preview {
+        Ok(mode) => Spans::from(format!("Result:      {} ({:04o})", format_mode(*mode), mode)),
+        Err(err) => Spans::from(Span::styled(err.clone(), Style::default().fg(Color::Red))),
+    });
+
+    for (field, label, input) in [
+        (ChmodField::Mode, "Mode:        ", &dialog.rule),
+        (ChmodField::DirMode, "Directories: ", &dialog.dir_rule),
+    ] {
+        let mut spans = vec![Span::styled(label, focused(field))];
+        if dialog.focus == field {
+            spans.extend(draw_text_input(input).0);
+        }
+        else if input.text().is_empty() {
+            spans.push(Span::styled("same as files", Style::default().fg(Color::DarkGray)));
+        }
+        else {
+            spans.push(Span::raw(input.text().to_string()));
+        }
+
+        lines.push(Spans::from(spans));
+    }
+
+    lines.push(Spans::from(format!("Recursive:   {}", if dialog.recursive { "on" } else { "off" })));
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        "[Tab] Field  [Arrows/Space] Toggle  [Ctrl+r] Recursive  [Enter] Apply  [Esc] Cancel",
+        Style::default().fg(Color::Gray),
+    )));
+
+    let area = centered_rect(rect.size(), 60, lines.len() as u16 + 2);
+    let paragraph = Paragraph::new(lines)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .style(Style::default().fg(Color::LightCyan)),
+        );
+
+    rect.render_widget(Clear, area);
+    rect.render_widget(paragraph, area);
+}
+
+/// Everything `stat` would say about the selected entry
+fn draw_properties<'a>(item: Option<&File>, owners: &Owners, note: Option<&str>) -> Paragraph<'a> {
+    let block = Block::default()
+        .title("Properties")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain)
+        .style(Style::default().fg(Color::White));
+
+    let item = match item {
+        Some(item) => item,
+        None => return Paragraph::new("Nothing selected").block(block),
+    };
+
+    let metadata = &item.metadata;
+    let label = |label: &str| Span::styled(format!("{:10}", label), Style::default().fg(Color::LightCyan));
+    let line = |name: &str, value: String| Spans::from(vec![label(name), Span::raw(value)]);
+    let time = |name: &str, time: Option<SystemTime>| match time {
+        Some(time) => vec![line(name, format_time(time)), line("", format_relative(time))],
+        None => vec![line(name, String::from("not available"))],
+    };
+
+    let dev = metadata.dev;
+    // glibc's encoding of major and minor numbers
+    let (major, minor) = ((dev >> 8) & 0xfff | (dev >> 32) & !0xfff, dev & 0xff | (dev >> 12) & !0xff);
+
+    let mut lines = vec![
+        line("Name", item.name.to_string_lossy().to_string()),
+        line("Type", type_name(item).to_string()),
+    ];
+
+    if let Some(link) = &item.link {
+        let broken = if item.is_broken_link() { " (broken)" } else { "" };
+        lines.push(line("Target", format!("{}{}", link.target.display(), broken)));
+    }
+
+    lines.extend([
+        line("Size", format!("{} bytes ({})", metadata.size, human_size(metadata.size))),
+        line("Blocks", format!("{} ({} allocated)", metadata.blocks, human_size(metadata.blocks * 512))),
+        line("Inode", metadata.ino.to_string()),
+        line("Device", format!("{},{}", major, minor)),
+        line("Links", metadata.nlink.to_string()),
+        line("Mode", format!("{}{} ({:04o})", type_char(item), format_mode(metadata.mode), metadata.mode)),
+        line("Owner", format!("{} ({})", owners.user(metadata.uid), metadata.uid)),
+        line("Group", format!("{} ({})", owners.group(metadata.gid), metadata.gid)),
+    ]);
+
+    lines.extend(time("Accessed", metadata.accessed));
+    lines.extend(time("Modified", metadata.modified));
+    lines.extend(time("Changed", metadata.changed));
+    lines.extend(time("Created", metadata.created));
+
+    if let Some(note) = note {
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(label("Note")));
+        lines.extend(note.lines().map(|line| Spans::from(line.to_string())));
+    }
+
+    Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(block)
+}
+
+fn type_name(item: &File) -> &'static str {
+    match type_char(item) {
+        'l' => "symbolic link",
+        'd' => "directory",
+        'p' => "fifo",
+        's' => "socket",
+        'c' => "character device",
+        'b' => "block device",
+        _ => "regular file",
+    }
+}
+
+/// First letter of an `ls -l` line
+fn type_char(item: &File) -> char {
+    match item.metadata.kind {
+        EntryKind::Symlink => 'l',
+        EntryKind::Dir => 'd',
+        EntryKind::Fifo => 'p',
+        EntryKind::Socket => 's',
+        EntryKind::CharDevice => 'c',
+        EntryKind::BlockDevice => 'b',
+        EntryKind::File => '-',
+    }
+}
+
+fn draw_xattrs<B>(rect: &mut Frame<B>, dialog: &XattrDialog)
+    where
+        B: Backend,
+{
+    let size = rect.size();
+    let area = centered_rect(size, 80, size.height.saturating_sub(6));
+    let block = Block::default()
+        .title(format!("Extended attributes of {}", dialog.path.file_name().unwrap_or_default().to_string_lossy()))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::LightCyan));
+
+    let inner = block.inner(area);
+    rect.render_widget(Clear, area);
+    rect.render_widget(block, area);
+
+    let list_height = (dialog.attrs.len() as u16).clamp(1, 8) + 1;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(list_height), Constraint::Min(3), Constraint::Length(1)].as_ref())
+        .split(inner);
+
+    if dialog.attrs.is_empty() {
+        rect.render_widget(Paragraph::new("No extended attributes").style(Style::default().fg(Color::Gray)), chunks[0]);
+    }
+    else {
+        let items: Vec<ListItem> = dialog.attrs.iter()
+            .map(|attr| ListItem::new(format!("{}  ({})", attr.name.to_string_lossy(), human_size(attr.value.len() as u64))))
+            .collect();
+
+        let mut state = ListState::default();
+        state.select(Some(dialog.selected));
+        rect.render_stateful_widget(draw_list(items).block(Block::default()).highlight_style(Style::default().add_modifier(Modifier::REVERSED)), chunks[0], &mut state);
+    }
+
+    if let Some(attr) = dialog.current() {
+        let lines: Vec<Spans> = if dialog.hex || !xattrs::is_text(&attr.value) {
+            xattrs::hex_dump(&attr.value).into_iter().map(Spans::from).collect()
+        }
+        else {
+            String::from_utf8_lossy(&attr.value).lines().map(|line| Spans::from(line.to_string())).collect()
+        };
+
+        let value = Paragraph::new(lines)
+            .style(Style::default().fg(Color::White))
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title("Value").borders(Borders::TOP));
+        rect.render_widget(value, chunks[1]);
+    }
+
+    let hint = Paragraph::new("[Up/Down] Select  [h] Hex  [a] Add  [e] Edit  [d] Remove  [Esc] Close")
+        .style(Style::default().fg(Color::Gray));
+    rect.render_widget(hint, chunks[2]);
+}
+
+fn draw_path_list<B>(rect: &mut Frame<B>, dialog: &PathListDialog)
+    where
+        B: Backend,
+{
+    let size = rect.size();
+    let area = centered_rect(size, 80, size.height.saturating_sub(6));
+    let block = Block::default()
+        .title(dialog.title.as_str())
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::LightCyan));
+
+    let inner = block.inner(area);
+    rect.render_widget(Clear, area);
+    rect.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(inner);
+
+    if dialog.items.is_empty() {
+        rect.render_widget(Paragraph::new("Nothing found").style(Style::default().fg(Color::Gray)), chunks[0]);
+    }
+    else {
+        let items: Vec<ListItem> = dialog.items.iter()
+            .map(|(path, detail)| {
+                ListItem::new(Spans::from(vec![
+                    Span::raw(path.to_string_lossy().to_string()),
+                    Span::styled(format!("  {}", detail), Style::default().fg(Color::Gray)),
+                ]))
+                .style(Style::default().fg(Color::White))
+            })
+            .collect();
+
+        let mut state = ListState::default();
+        state.select(Some(dialog.selected));
+        rect.render_stateful_widget(draw_list(items).block(Block::default()).highlight_style(Style::default().add_modifier(Modifier::REVERSED)), chunks[0], &mut state);
+    }
+
+    let hint = Paragraph::new("[Up/Down] Select  [Enter] Go to file  [Esc] Close")
+        .style(Style::default().fg(Color::Gray));
+    rect.render_widget(hint, chunks[1]);
+}
+
+/// Each tag as a chip, coloured by its name so a tag looks the same everywhere
+fn tag_chips(tags: &[String]) -> Vec<Span<'static>> {
+    const PALETTE: [Color; 6] = [Color::Blue, Color::Magenta, Color::Cyan, Color::Green, Color::Yellow, Color::Red];
+
+    let mut spans = Vec::new();
+    for tag in tags {
+        let hash = tag.bytes().fold(0usize, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as usize));
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!(" {} ", tag), Style::default().fg(Color::Black).bg(PALETTE[hash % PALETTE.len()])));
     }
+
+    spans
+}
+
+/// Text with the char under the cursor drawn reversed
+fn draw_text_input(input: &TextInput) -> Spans<'static> {
+    let mut chars = input.text().chars();
+    let before: String = chars.by_ref().take(input.cursor()).collect();
+    let under = chars.next().map_or(String::from(" "), String::from);
+    let after: String = chars.collect();
+
+    Spans::from(vec![
+        Span::raw(before),
+        Span::styled(under, Style::default().add_modifier(Modifier::REVERSED)),
+        Span::raw(after),
+    ])
+}
+
+fn draw_candidate<'a>(vfs: &dyn Vfs, title: &'a str, path: &Path) -> Paragraph<'a> {
+    let detail = match vfs.symlink_stat(path) {
+        Ok(stat) => format!(
+            "{}\nSize: {}\nModified: {}",
+            path.display(),
+            human_size(stat.size),
+            stat.modified.map(format_time).unwrap_or_default(),
+        ),
+        Err(err) => format!("{}\n{}", path.display(), err),
+    };
+
+    Paragraph::new(detail)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL),
+        )
+}
+
+/// Area of `percent_x` of the width and `height` rows in the middle of `area`
+fn centered_rect(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = height.min(area.height);
+
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
 }
 
 fn draw_title(title: &str) -> Paragraph {
@@ -126,13 +831,47 @@ fn draw_title(title: &str) -> Paragraph {
         )
 }
 
-fn draw_help<'a>() -> Paragraph<'a> {
-    Paragraph::new("[q] Quit\n")
+/// One line per action, descriptions wider than `width` wrap under themselves
+fn help_lines(actions: &[Action], width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for action in actions {
+        let keys = action.keys()
+            .iter()
+            .map(|key| key.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut line = format!("{:16} ", keys);
+        for (index, word) in action.to_string().split(' ').enumerate() {
+            if index == 0 {
+                line.push_str(word);
+            }
+            else if line.len() + 1 + word.len() > width {
+                lines.push(line);
+                line = format!("{:16} {}", "", word);
+            }
+            else {
+                line.push(' ');
+                line.push_str(word);
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Help from line `scroll` on, the title tells how to scroll when it doesn't fit
+fn draw_help<'a>(lines: Vec<String>, scroll: u16, scrollable: bool) -> Paragraph<'a> {
+    let title = if scrollable { "Help [Up/Down PgUp/PgDn to scroll]" } else { "Help" };
+
+    Paragraph::new(lines.join("\n"))
         .style(Style::default().fg(Color::White))
         .alignment(Alignment::Left)
+        .scroll((scroll, 0))
         .block(
             Block::default()
-                .title("Help")
+                .title(title)
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded),
         )
@@ -151,6 +890,113 @@ fn draw_list(list_items:Vec<ListItem>) -> List {
         )
 }
 
+fn draw_jobs<'a>(jobs: &[Job], selected: usize) -> (List<'a>, ListState) {
+    let mut job_items: Vec<ListItem> = Vec::new();
+
+    for job in jobs {
+        let progress = &job.progress;
+        let bytes_done = progress.bytes_done.load(Ordering::Relaxed);
+        let bytes_total = progress.bytes_total.load(Ordering::Relaxed);
+        let ratio = if bytes_total == 0 { 0.0 } else { bytes_done as f64 / bytes_total as f64 };
+
+        let bar_width = 20;
+        let filled = (ratio * bar_width as f64).round() as usize;
+        let eta = match job.eta() {
+            Some(eta) => format_duration(eta),
+            None => String::from("--:--:--"),
+        };
+
+        let header = format!(
+            "#{} {:?} {} item(s) [{:?}]",
+            job.id, job.kind, job.transfers.len(), job.status()
+        );
+        let detail = format!(
+            "[{}{}] {:3.0}%  {}/{} files  {}/{}  {}/s  ETA {}",
+            "#".repeat(filled),
+            "-".repeat(bar_width - filled),
+            ratio * 100.0,
+            progress.files_done.load(Ordering::Relaxed),
+            progress.files_total.load(Ordering::Relaxed),
+            human_size(bytes_done),
+            human_size(bytes_total),
+            human_size(job.throughput() as u64),
+            eta,
+        );
+
+        job_items.push(ListItem::new(format!("{}\n{}", header, detail)));
+    }
+
+    if job_items.is_empty() {
+        job_items.push(ListItem::new("No running jobs"));
+    }
+
+    let mut state = ListState::default();
+    if !jobs.is_empty() {
+        state.select(Some(selected));
+    }
+
+    let list = List::new(job_items)
+        .block(
+            Block::default()
+                .title("Jobs")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        );
+
+    (list, state)
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    }
+    else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_time(time: SystemTime) -> String {
+    let datetime: DateTime<Utc> = time.into();
+    datetime.format("%d/%m/%Y %T").to_string()
+}
+
+/// How long ago `time` was, like "3 hours ago"
+fn format_relative(time: SystemTime) -> String {
+    let (secs, suffix) = match SystemTime::now().duration_since(time) {
+        Ok(elapsed) => (elapsed.as_secs(), "ago"),
+        Err(err) => (err.duration().as_secs(), "from now"),
+    };
+
+    let (count, unit) = match secs {
+        0..=59 => return String::from("just now"),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86_399 => (secs / 3600, "hour"),
+        86_400..=2_591_999 => (secs / 86_400, "day"),
+        2_592_000..=31_535_999 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+
+    format!("{} {}{} {}", count, unit, if count == 1 { "" } else { "s" }, suffix)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
 fn draw_logs<'a>() -> TuiLoggerWidget<'a> {
     TuiLoggerWidget::default()
         .style_error(Style::default().fg(Color::Red))
@@ -0,0 +1,164 @@
+use std::fs;
+use std::fs::Metadata;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::fs::{lchown, FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+
+use crate::app::jobs::JobProgress;
+use crate::app::vfs::{EntryKind, Stat, Vfs};
+use crate::app::xattrs;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The local disk through `std::fs`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFs;
+
+impl Vfs for LocalFs {
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        // entries that vanish while being read are skipped
+        Ok(fs::read_dir(dir)?.flatten().map(|entry| entry.path()).collect())
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<Stat> {
+        fs::metadata(path).map(|metadata| Stat::from(&metadata))
+    }
+
+    fn symlink_stat(&self, path: &Path) -> io::Result<Stat> {
+        fs::symlink_metadata(path).map(|metadata| Stat::from(&metadata))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        fs::write(path, data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir_all(path)
+        }
+        else {
+            fs::remove_file(path)
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn create_file(&self, path: &Path) -> io::Result<()> {
+        fs::OpenOptions::new().write(true).create_new(true).open(path).map(|_| ())
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, path)
+    }
+
+    fn hard_link(&self, target: &Path, path: &Path) -> io::Result<()> {
+        fs::hard_link(target, path)
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    fn set_owner(&self, path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+        lchown(path, Some(uid), Some(gid))
+    }
+
+    fn xattr(&self, path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+        xattr::get(path, name)
+    }
+
+    fn copy_xattrs(&self, from: &Path, to: &Path) -> io::Result<()> {
+        xattrs::copy(from, to)
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path, progress: &JobProgress) -> io::Result<()> {
+        let mut reader = fs::File::open(from)?;
+        let mut writer = fs::File::create(to)?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+
+        loop {
+            progress.checkpoint()?;
+
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+
+            writer.write_all(&buffer[..read])?;
+            progress.bytes_done.fetch_add(read as u64, Ordering::Relaxed);
+        }
+
+        Ok(())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+impl From<&Metadata> for Stat {
+    fn from(metadata: &Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            EntryKind::Symlink
+        }
+        else if file_type.is_dir() {
+            EntryKind::Dir
+        }
+        else if file_type.is_fifo() {
+            EntryKind::Fifo
+        }
+        else if file_type.is_socket() {
+            EntryKind::Socket
+        }
+        else if file_type.is_char_device() {
+            EntryKind::CharDevice
+        }
+        else if file_type.is_block_device() {
+            EntryKind::BlockDevice
+        }
+        else {
+            EntryKind::File
+        };
+
+        let changed = SystemTime::UNIX_EPOCH
+            .checked_add(Duration::new(metadata.ctime().max(0) as u64, metadata.ctime_nsec() as u32));
+
+        Stat {
+            kind,
+            mode: metadata.mode() & 0o7777,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.len(),
+            blocks: metadata.blocks(),
+            nlink: metadata.nlink(),
+            ino: metadata.ino(),
+            dev: metadata.dev(),
+            accessed: metadata.accessed().ok(),
+            modified: metadata.modified().ok(),
+            changed,
+            created: metadata.created().ok(),
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! A filesystem held in memory, for tests that must not depend on the disk.
+//!
+//! It is built from a fixture with one entry per line, its path relative to `/`:
+//!
+//! ```text
+//! docs/                               directory
+//! docs/empty                          empty file
+//! docs/a.txt = first\nsecond          file and its content, `\n` starts a new line
+//! docs/b.txt 0600 @1700000000 = x     mode in octal and mtime in seconds
+//! docs/link -> a.txt                  symlink
+//! ```
+//!
+//! Missing parents are created as directories, blank lines and `#` comments are
+//! skipped. Entries are listed by name and times only change when the fixture sets them.
+//! Every entry is owned by root, hard links and extended attributes are not supported.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use crate::app::jobs::JobProgress;
+use crate::app::vfs::{EntryKind, Stat, Vfs};
+
+/// Symlinks followed while resolving one path, as Linux does
+const MAX_LINKS: usize = 40;
+
+#[derive(Debug, Clone)]
+enum Content {
+    File(Vec<u8>),
+    Dir,
+    Symlink(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    content: Content,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    modified: SystemTime,
+    ino: u64,
+}
+
+#[derive(Debug)]
+pub struct MemoryFs {
+    /// Every entry by absolute path, `/` included
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+}
+
+impl MemoryFs {
+    pub fn from_fixture(fixture: &str) -> Result<Self, String> {
+        let fs = MemoryFs { nodes: Mutex::new(BTreeMap::new()) };
+        fs.insert(PathBuf::from("/"), Content::Dir, None, None);
+
+        for (number, line) in fixture.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            fs.parse_line(line).map_err(|err| format!("Fixture line {}: {}", number + 1, err))?;
+        }
+
+        Ok(fs)
+    }
+
+    fn parse_line(&self, line: &str) -> Result<(), String> {
+        let (spec, text) = match line.split_once(" = ") {
+            Some((spec, text)) => (spec, Some(text)),
+            None => (line, None),
+        };
+        let (spec, target) = match spec.split_once(" -> ") {
+            Some((spec, target)) => (spec, Some(target.trim())),
+            None => (spec, None),
+        };
+
+        let mut words = spec.split_whitespace();
+        let name = words.next().ok_or("no path")?;
+
+        let mut mode = None;
+        let mut modified = None;
+        for word in words {
+            if let Some(secs) = word.strip_prefix('@') {
+                let secs = secs.parse().map_err(|_| format!("'{}' is not a time in seconds", word))?;
+                modified = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+            }
+            else {
+                mode = Some(u32::from_str_radix(word, 8).map_err(|_| format!("'{}' is not an octal mode", word))?);
+            }
+        }
+
+        let content = match (name.ends_with('/'), target, text) {
+            (true, None, None) => Content::Dir,
+            (false, Some(target), None) => Content::Symlink(PathBuf::from(target)),
+            (false, None, text) => Content::File(unescape(text.unwrap_or_default()).into_bytes()),
+            _ => return Err(format!("'{}' is more than one kind of entry", name)),
+        };
+
+        let path = Path::new("/").join(name.trim_end_matches('/'));
+        for parent in path.ancestors().skip(1) {
+            if !self.nodes().contains_key(parent) {
+                self.insert(parent.to_path_buf(), Content::Dir, None, None);
+            }
+        }
+
+        self.insert(path, content, mode, modified);
+        Ok(())
+    }
+
+    fn insert(&self, path: PathBuf, content: Content, mode: Option<u32>, modified: Option<SystemTime>) {
+        let mode = mode.unwrap_or(match content {
+            Content::File(_) => 0o644,
+            Content::Dir => 0o755,
+            Content::Symlink(_) => 0o777,
+        });
+
+        let mut nodes = self.nodes();
+        let ino = nodes.values().map(|node| node.ino).max().unwrap_or(1) + 1;
+        let modified = modified.unwrap_or(SystemTime::UNIX_EPOCH);
+
+        nodes.insert(path, Node { content, mode, uid: 0, gid: 0, modified, ino });
+    }
+
+    fn nodes(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes.lock().unwrap()
+    }
+
+    /// Where `path` is stored, following symlinks in its parents and,
+    /// with `follow`, the entry itself
+    fn resolve(&self, path: &Path, follow: bool) -> io::Result<PathBuf> {
+        self.resolve_within(path, follow, MAX_LINKS)
+    }
+
+    fn resolve_within(&self, path: &Path, follow: bool, links: usize) -> io::Result<PathBuf> {
+        let components: Vec<Component> = path.components().collect();
+        let mut resolved = PathBuf::from("/");
+
+        for (index, component) in components.iter().enumerate() {
+            let name = match component {
+                Component::Normal(name) => name,
+                Component::ParentDir => {
+                    resolved.pop();
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let next = resolved.join(name);
+            let is_last = index + 1 == components.len();
+
+            let target = match self.nodes().get(&next) {
+                None => return Err(not_found(path)),
+                Some(Node { content: Content::Symlink(target), .. }) if follow || !is_last => target.clone(),
+                Some(_) => {
+                    resolved = next;
+                    continue;
+                }
+            };
+
+            if links == 0 {
+                return Err(io::Error::other(format!("{}: too many levels of symbolic links", path.display())));
+            }
+            // an absolute target replaces what was resolved so far
+            resolved = self.resolve_within(&resolved.join(target), true, links - 1)?;
+        }
+
+        Ok(resolved)
+    }
+
+    fn node(&self, path: &Path, follow: bool) -> io::Result<Node> {
+        let resolved = self.resolve(path, follow)?;
+        self.nodes().get(&resolved).cloned().ok_or_else(|| not_found(path))
+    }
+
+    /// Store a new entry at `path`, which must not exist yet
+    fn create(&self, path: &Path, content: Content) -> io::Result<()> {
+        let resolved = self.resolve_new(path)?;
+
+        if self.nodes().contains_key(&resolved) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path.display())));
+        }
+
+        self.insert(resolved, content, None, None);
+        Ok(())
+    }
+
+    /// Change the entry at `path` in place
+    fn update(&self, path: &Path, follow: bool, change: impl FnOnce(&mut Node)) -> io::Result<()> {
+        let resolved = self.resolve(path, follow)?;
+
+        match self.nodes().get_mut(&resolved) {
+            Some(node) => {
+                change(node);
+                Ok(())
+            }
+            None => Err(not_found(path)),
+        }
+    }
+
+    /// Where a new entry at `path` would be stored, its parent must be a directory
+    fn resolve_new(&self, path: &Path) -> io::Result<PathBuf> {
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no parent", path.display()))),
+        };
+
+        match self.node(parent, true)?.content {
+            Content::Dir => Ok(self.resolve(parent, true)?.join(name)),
+            _ => Err(io::Error::new(io::ErrorKind::NotADirectory, format!("{} is not a directory", parent.display()))),
+        }
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let resolved = self.resolve(dir, true)?;
+        let nodes = self.nodes();
+
+        match nodes.get(&resolved) {
+            Some(Node { content: Content::Dir, .. }) => {}
+            Some(_) => return Err(io::Error::new(io::ErrorKind::NotADirectory, format!("{} is not a directory", dir.display()))),
+            None => return Err(not_found(dir)),
+        }
+
+        Ok(nodes.keys()
+            .filter(|path| path.parent() == Some(resolved.as_path()))
+            .filter_map(|path| path.file_name())
+            .map(|name| dir.join(name))
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<Stat> {
+        self.node(path, true).map(|node| stat(&node))
+    }
+
+    fn symlink_stat(&self, path: &Path) -> io::Result<Stat> {
+        self.node(path, false).map(|node| stat(&node))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.node(path, false)?.content {
+            Content::Symlink(target) => Ok(target),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a symlink", path.display()))),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.resolve(path, true)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.node(path, true)?.content {
+            Content::File(data) => Ok(data),
+            _ => Err(io::Error::new(io::ErrorKind::IsADirectory, format!("{} is not a file", path.display()))),
+        }
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let resolved = match self.resolve(path, true) {
+            Ok(resolved) => resolved,
+            Err(_) => self.resolve_new(path)?,
+        };
+
+        let existing = self.nodes().get(&resolved).cloned();
+        match existing {
+            Some(Node { content: Content::File(_), .. }) => {
+                if let Some(node) = self.nodes().get_mut(&resolved) {
+                    node.content = Content::File(data.to_vec());
+                }
+            }
+            Some(_) => return Err(io::Error::new(io::ErrorKind::IsADirectory, format!("{} is not a file", path.display()))),
+            None => self.insert(resolved, Content::File(data.to_vec()), None, None),
+        }
+
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let from = self.resolve(from, false)?;
+        let to = self.resolve_new(to)?;
+
+        if to == from {
+            return Ok(());
+        }
+        if to.starts_with(&from) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot move {} into itself", from.display())));
+        }
+
+        let mut nodes = self.nodes();
+        if nodes.keys().any(|path| path.starts_with(&to) && path != &to) {
+            return Err(io::Error::new(io::ErrorKind::DirectoryNotEmpty, format!("{} is not empty", to.display())));
+        }
+        nodes.remove(&to);
+
+        let moved: Vec<PathBuf> = nodes.keys().filter(|path| path.starts_with(&from)).cloned().collect();
+        for path in moved {
+            if let Some(node) = nodes.remove(&path) {
+                let relative = path.strip_prefix(&from).unwrap_or(Path::new(""));
+                nodes.insert(if relative.as_os_str().is_empty() { to.clone() } else { to.join(relative) }, node);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let resolved = self.resolve(path, false)?;
+        if resolved == Path::new("/") {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Cannot remove /"));
+        }
+
+        self.nodes().retain(|path, _| !path.starts_with(&resolved));
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.create(path, Content::Dir)
+    }
+
+    fn create_file(&self, path: &Path) -> io::Result<()> {
+        self.create(path, Content::File(Vec::new()))
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        self.create(path, Content::Symlink(target.to_path_buf()))
+    }
+
+    fn hard_link(&self, _target: &Path, path: &Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, format!("Cannot hard link {}, not supported in memory", path.display())))
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        self.update(path, true, |node| node.mode = mode & 0o7777)
+    }
+
+    fn set_owner(&self, path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+        self.update(path, false, |node| {
+            node.uid = uid;
+            node.gid = gid;
+        })
+    }
+
+    fn xattr(&self, path: &Path, _name: &str) -> io::Result<Option<Vec<u8>>> {
+        self.node(path, true).map(|_| None)
+    }
+
+    fn copy_xattrs(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path, progress: &JobProgress) -> io::Result<()> {
+        progress.checkpoint()?;
+
+        let data = self.read(from)?;
+        self.write(to, &data)?;
+        progress.bytes_done.fetch_add(data.len() as u64, Ordering::Relaxed);
+
+        Ok(())
+    }
+}
+
+fn stat(node: &Node) -> Stat {
+    let (kind, size, nlink) = match &node.content {
+        Content::File(data) => (EntryKind::File, data.len() as u64, 1),
+        Content::Dir => (EntryKind::Dir, 4096, 2),
+        Content::Symlink(target) => (EntryKind::Symlink, target.as_os_str().len() as u64, 1),
+    };
+
+    Stat {
+        kind,
+        mode: node.mode,
+        uid: node.uid,
+        gid: node.gid,
+        size,
+        blocks: size.div_ceil(512),
+        nlink,
+        ino: node.ino,
+        dev: 1,
+        accessed: Some(node.modified),
+        modified: Some(node.modified),
+        changed: Some(node.modified),
+        created: None,
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display()))
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("\\n", "\n")
+}
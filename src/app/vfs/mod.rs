@@ -0,0 +1,136 @@
+//! Filesystem access behind `AppState`.
+//!
+//! Listings and file operations go through a `Vfs` rather than `std::fs`, so
+//! anything that can list and change entries can be browsed. The local disk is
+//! the default, actions that need real paths are refused on any other backend.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::app::jobs::JobProgress;
+
+pub mod local;
+pub mod memory;
+
+pub use local::LocalFs;
+pub use memory::MemoryFs;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    CharDevice,
+    BlockDevice,
+}
+
+/// What `stat` says about an entry, the same whichever backend it came from
+#[derive(Debug, Clone)]
+pub struct Stat {
+    pub kind: EntryKind,
+    /// Permission bits with setuid, setgid and sticky, without the type
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    /// 512 byte blocks allocated
+    pub blocks: u64,
+    pub nlink: u64,
+    pub ino: u64,
+    pub dev: u64,
+    pub accessed: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    /// Last status change
+    pub changed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+}
+
+impl Stat {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == EntryKind::Symlink
+    }
+}
+
+pub trait Vfs: Send + Sync {
+    /// Paths of the entries in `dir`, in no particular order
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Stat of what `path` finally points to, following symlinks
+    fn stat(&self, path: &Path) -> io::Result<Stat>;
+
+    /// Stat of the entry itself, a symlink is not followed
+    fn symlink_stat(&self, path: &Path) -> io::Result<Stat>;
+
+    /// Path stored in the symlink at `path`
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Absolute path of `path` with every symlink resolved
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Create or replace the file at `path` with `data`
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Remove a file, a symlink or a whole directory tree
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    /// Create an empty directory, failing if `path` exists
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// Create an empty file, failing if `path` exists
+    fn create_file(&self, path: &Path) -> io::Result<()>;
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()>;
+
+    fn hard_link(&self, target: &Path, path: &Path) -> io::Result<()>;
+
+    /// Set the permission bits of what `path` points to
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// Set the owner of the entry itself, a symlink is not followed
+    fn set_owner(&self, path: &Path, uid: u32, gid: u32) -> io::Result<()>;
+
+    /// Value of the extended attribute `name`, `None` when it is not set
+    fn xattr(&self, path: &Path, name: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Copy every extended attribute the target accepts
+    fn copy_xattrs(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Copy the data of a file, adding what was written to `progress`
+    fn copy_file(&self, from: &Path, to: &Path, progress: &JobProgress) -> io::Result<()>;
+
+    /// Paths are real paths on this machine, which archives, xattrs, notes and
+    /// templates need
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// Create `dir` and its missing parents, like `mkdir -p`
+pub fn create_dir_all(vfs: &dyn Vfs, dir: &Path) -> io::Result<()> {
+    match vfs.stat(dir) {
+        Ok(stat) if stat.is_dir() => return Ok(()),
+        Ok(_) => return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is not a directory", dir.display()))),
+        Err(_) => {}
+    }
+
+    if let Some(parent) = dir.parent() {
+        create_dir_all(vfs, parent)?;
+    }
+
+    match vfs.create_dir(dir) {
+        // created by someone else in the meantime
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        result => result,
+    }
+}
@@ -0,0 +1,127 @@
+//! Extended attributes of a single entry, symlinks are never followed.
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::Path;
+
+/// Filesystem without xattr support, Linux value
+const EOPNOTSUPP: i32 = 95;
+
+#[derive(Debug, Clone)]
+pub struct Xattr {
+    pub name: OsString,
+    pub value: Vec<u8>,
+}
+
+/// Every attribute of `path` with its value, sorted by name
+pub fn list(path: &Path) -> io::Result<Vec<Xattr>> {
+    let mut attrs = Vec::new();
+
+    for name in xattr::list(path)? {
+        // it may be removed between listing and reading
+        if let Some(value) = xattr::get(path, &name)? {
+            attrs.push(Xattr { name, value });
+        }
+    }
+
+    attrs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(attrs)
+}
+
+pub fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    xattr::set(path, name, value)
+}
+
+pub fn remove(path: &Path, name: &OsStr) -> io::Result<()> {
+    xattr::remove(path, name)
+}
+
+/// Copy every attribute of `from` onto `to`.
+///
+/// Attributes the target refuses, like `security.*` ones without privileges or
+/// any on a filesystem without xattr support, are skipped rather than failing the copy.
+pub fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    let attrs = match list(from) {
+        Ok(attrs) => attrs,
+        Err(err) if is_refusal(&err) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    for attr in attrs {
+        match xattr::set(to, &attr.name, &attr.value) {
+            Err(err) if !is_refusal(&err) => return Err(err),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn is_refusal(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::PermissionDenied || is_unsupported(err)
+}
+
+/// The filesystem can't hold extended attributes at all
+pub fn is_unsupported(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::Unsupported || err.raw_os_error() == Some(EOPNOTSUPP)
+}
+
+/// Names without a namespace go to `user.`, the only one open to everybody
+pub fn full_name(name: &str) -> String {
+    if name.contains('.') {
+        name.to_string()
+    }
+    else {
+        format!("user.{}", name)
+    }
+}
+
+/// Printable UTF-8, shown as text rather than hex
+pub fn is_text(value: &[u8]) -> bool {
+    std::str::from_utf8(value).is_ok_and(|text| text.chars().all(|c| !c.is_control() || c == '\n' || c == '\t'))
+}
+
+/// Value as typed in the editor: text, or hex bytes after `0x`
+pub fn parse_value(text: &str) -> Result<Vec<u8>, String> {
+    let hex = match text.strip_prefix("0x") {
+        Some(hex) => hex,
+        None => return Ok(text.as_bytes().to_vec()),
+    };
+
+    let digits: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(String::from("Hex value needs an even number of digits"));
+    }
+
+    digits.chunks(2)
+        .map(|pair| {
+            let byte: String = pair.iter().collect();
+            u8::from_str_radix(&byte, 16).map_err(|_| format!("'{}' is not a hex byte", byte))
+        })
+        .collect()
+}
+
+/// Value as it would be typed back into the editor
+pub fn edit_text(value: &[u8]) -> String {
+    if is_text(value) && !value.starts_with(b"0x") {
+        String::from_utf8_lossy(value).to_string()
+    }
+    else {
+        format!("0x{}", value.iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+    }
+}
+
+/// `hexdump -C` style lines
+pub fn hex_dump(value: &[u8]) -> Vec<String> {
+    value.chunks(16)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let ascii: String = chunk.iter()
+                .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+                .collect();
+
+            format!("{:08x}  {:<47}  |{}|", index * 16, hex.join(" "), ascii)
+        })
+        .collect()
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, RecvError, Sender};
 use std::thread;
 use std::time::Duration;
@@ -8,26 +10,48 @@ use crate::inputs::key::Key;
 pub struct Events {
     rx: Receiver<InputEvent>,
     _tx: Sender<InputEvent>,
+    /// Stop reading the terminal while another program owns it
+    paused: Arc<AtomicBool>,
+    tick_rate: Duration,
 }
 
 impl Events {
     pub fn new(tick_rate: Duration) -> Events {
         let (tx, rx) = channel();
         let event_tx = tx.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let thread_paused = paused.clone();
         thread::spawn(move || {
             loop {
+                if thread_paused.load(Ordering::Relaxed) {
+                    thread::sleep(tick_rate);
+                    continue;
+                }
+
                 if event::poll(tick_rate).unwrap() {
                     if let crossterm::event::Event::Key(key) = event::read().unwrap() {
                         let key = Key::from(key);
                         event_tx.send(InputEvent::Input(key)).unwrap();
                     }
-
-                    event_tx.send(InputEvent::Tick).unwrap();
                 }
+
+                // tick even when idle so background jobs get polled
+                event_tx.send(InputEvent::Tick).unwrap();
             }
         });
 
-        Events {rx, _tx: tx}
+        Events {rx, _tx: tx, paused, tick_rate}
+    }
+
+    /// Leave key presses to another program, like an editor
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+        // let a poll already in flight run out before handing the terminal over
+        thread::sleep(self.tick_rate);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
     }
 
     pub fn next(&self) -> Result<InputEvent, RecvError> {
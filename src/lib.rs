@@ -1,5 +1,7 @@
 use std::cell::RefCell;
-use std::io::stdout;
+use std::io::{stdout, Stdout};
+use std::path::Path;
+use std::process::ExitStatus;
 use std::rc::Rc;
 use std::time::Duration;
 
@@ -9,6 +11,8 @@ use tui::backend::CrosstermBackend;
 use tui::Terminal;
 
 use crate::app::{AppReturn, ui};
+use crate::app::archive;
+use crate::app::name_edit::editor_command;
 use crate::inputs::event::Events;
 use crate::inputs::InputEvent;
 
@@ -38,11 +42,18 @@ pub fn start_ui(app: Rc<RefCell<App>>) -> Result<()> {
             InputEvent::Tick => app.update_on_tick(),
         };
 
-        if result == AppReturn::Exit {
-            break;
+        match result {
+            AppReturn::Exit => break,
+            AppReturn::RunEditor(file) => {
+                let status = run_editor(&mut terminal, &events, &file)?;
+                app.editor_closed(status);
+            }
+            AppReturn::Continue => {}
         }
     }
 
+    archive::clear_browse_cache();
+
     // Restore the terminal and close application
     terminal.clear()?;
     terminal.show_cursor()?;
@@ -50,3 +61,25 @@ pub fn start_ui(app: Rc<RefCell<App>>) -> Result<()> {
 
     Ok(())
 }
+
+/// Hand the terminal over to `$EDITOR` on `file` and take it back once it exits
+fn run_editor(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    events: &Events,
+    file: &Path,
+) -> Result<std::io::Result<ExitStatus>> {
+    events.pause();
+    terminal.clear()?;
+    terminal.show_cursor()?;
+    crossterm::terminal::disable_raw_mode()?;
+
+    let status = editor_command(file).status();
+
+    // clearing also resets tui's buffers so the next draw repaints everything
+    crossterm::terminal::enable_raw_mode()?;
+    terminal.clear()?;
+    terminal.hide_cursor()?;
+    events.resume();
+
+    Ok(status)
+}
@@ -0,0 +1,69 @@
+//! Shared helpers: an `App` over an in-memory tree, driven by keys and drawn headless.
+
+// every test file only uses some of the helpers
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use file_tui::app::state::AppState;
+use file_tui::app::vfs::MemoryFs;
+use file_tui::app::{ui, App, AppReturn};
+use file_tui::inputs::key::Key;
+use tui::backend::TestBackend;
+use tui::buffer::Buffer;
+use tui::Terminal;
+
+/// App browsing `/` of the tree described by `fixture`
+pub fn app(fixture: &str) -> App {
+    let vfs = Arc::new(MemoryFs::from_fixture(fixture).unwrap());
+    App::with_state(AppState::with_vfs(vfs, PathBuf::from("/")).unwrap())
+}
+
+/// Send `keys` one after the other, none of them may quit or hand over to the editor
+pub fn press(app: &mut App, keys: &[Key]) {
+    for key in keys {
+        assert_eq!(app.do_action(*key), AppReturn::Continue, "after {}", key);
+    }
+}
+
+/// Type `text` into whatever has the focus
+pub fn type_text(app: &mut App, text: &str) {
+    for c in text.chars() {
+        press(app, &[Key::Char(c)]);
+    }
+}
+
+/// Tick until every background job has finished and its result is applied
+pub fn finish_jobs(app: &mut App) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    while !app.jobs().jobs().is_empty() {
+        assert!(Instant::now() < deadline, "jobs still running");
+        thread::sleep(Duration::from_millis(5));
+        app.update_on_tick();
+    }
+}
+
+/// Send `keys`, then draw one frame on a `width` x `height` terminal
+pub fn render(app: &mut App, keys: &[Key], width: u16, height: u16) -> Buffer {
+    press(app, keys);
+
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(|rect| ui::draw(rect, app)).unwrap();
+
+    terminal.backend().buffer().clone()
+}
+
+/// Text of the buffer, one line per row with trailing blanks trimmed
+pub fn screen(buffer: &Buffer) -> String {
+    let width = buffer.area.width as usize;
+
+    buffer.content
+        .chunks(width)
+        .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect::<String>().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
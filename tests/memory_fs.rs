@@ -0,0 +1,333 @@
+mod common;
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use file_tui::app::trash::trash_dir;
+use file_tui::app::vfs::{EntryKind, MemoryFs, Vfs};
+use file_tui::app::App;
+use file_tui::inputs::key::Key;
+
+use common::{finish_jobs, press, type_text};
+
+const FIXTURE: &str = "
+    alpha/
+    beta/one = 1
+    beta/two = 2
+    beta/three = 3
+    beta/four = 4
+    gamma/
+    gamma/deep/
+    link -> beta
+    notes.txt 0600 @1700000000 = first\\nsecond
+";
+
+fn app() -> App {
+    common::app(FIXTURE)
+}
+
+fn cursor(app: &App) -> &Path {
+    app.state().cursor().unwrap()
+}
+
+fn index(app: &App) -> usize {
+    app.state().current_list().unwrap().index()
+}
+
+fn names(app: &App) -> Vec<String> {
+    app.state().current_list().unwrap().items.iter()
+        .map(|item| item.name.to_string_lossy().to_string())
+        .collect()
+}
+
+fn selected(app: &App) -> String {
+    app.state().selected().unwrap().name.to_string_lossy().to_string()
+}
+
+#[test]
+fn fixture_sets_kinds_modes_and_times() {
+    let fs = MemoryFs::from_fixture(FIXTURE).unwrap();
+
+    let notes = fs.symlink_stat(Path::new("/notes.txt")).unwrap();
+    assert_eq!(notes.kind, EntryKind::File);
+    assert_eq!(notes.mode, 0o600);
+    assert_eq!(notes.size, 12);
+    assert_eq!(notes.modified, Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)));
+    assert_eq!(fs.read(Path::new("/notes.txt")).unwrap(), b"first\nsecond");
+
+    assert_eq!(fs.symlink_stat(Path::new("/link")).unwrap().kind, EntryKind::Symlink);
+    assert_eq!(fs.read_link(Path::new("/link")).unwrap(), PathBuf::from("beta"));
+    assert!(fs.stat(Path::new("/link")).unwrap().is_dir());
+    assert_eq!(fs.read(Path::new("/link/two")).unwrap(), b"2");
+
+    // parents missing from the fixture are created
+    assert_eq!(fs.symlink_stat(Path::new("/gamma")).unwrap().mode, 0o755);
+    assert!(MemoryFs::from_fixture("dir/ -> elsewhere").is_err());
+    assert!(MemoryFs::from_fixture("file 9z").is_err());
+}
+
+#[test]
+fn listing_puts_directories_first() {
+    let app = app();
+
+    assert_eq!(cursor(&app), Path::new("/"));
+    assert_eq!(names(&app), ["alpha", "beta", "gamma", "link", "notes.txt"]);
+    assert_eq!(index(&app), 0);
+}
+
+#[test]
+fn back_selects_the_directory_left() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Down, Key::Down, Key::Right]);
+    assert_eq!(cursor(&app), Path::new("/gamma"));
+    assert_eq!(names(&app), ["deep"]);
+
+    press(&mut app, &[Key::Left]);
+    assert_eq!(cursor(&app), Path::new("/"));
+    assert_eq!(selected(&app), "gamma");
+}
+
+#[test]
+fn entering_again_restores_the_last_index() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Down, Key::Right]);
+    assert_eq!(cursor(&app), Path::new("/beta"));
+    assert_eq!(names(&app), ["four", "one", "three", "two"]);
+    assert_eq!(index(&app), 0);
+
+    press(&mut app, &[Key::Down, Key::Down, Key::Left]);
+    assert_eq!(selected(&app), "beta");
+
+    // every directory keeps its own position
+    press(&mut app, &[Key::Down, Key::Right]);
+    assert_eq!(cursor(&app), Path::new("/gamma"));
+    assert_eq!(index(&app), 0);
+
+    press(&mut app, &[Key::Left, Key::Up, Key::Right]);
+    assert_eq!(cursor(&app), Path::new("/beta"));
+    assert_eq!(index(&app), 2);
+    assert_eq!(selected(&app), "three");
+
+    // the parent remembers where it was left as well
+    press(&mut app, &[Key::Left, Key::Left]);
+    assert_eq!(cursor(&app), Path::new("/"));
+    assert_eq!(selected(&app), "beta");
+}
+
+#[test]
+fn last_index_past_the_end_falls_back_to_the_first_entry() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Down, Key::Right, Key::Up]);
+    assert_eq!(index(&app), 3);
+    press(&mut app, &[Key::Left]);
+
+    let vfs = app.state().vfs().unwrap();
+    vfs.remove(Path::new("/beta/two")).unwrap();
+    vfs.remove(Path::new("/beta/one")).unwrap();
+
+    press(&mut app, &[Key::Right]);
+    assert_eq!(names(&app), ["four", "three"]);
+    assert_eq!(index(&app), 0);
+}
+
+#[test]
+fn symlinked_directory_is_entered_under_its_own_path() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Down, Key::Down, Key::Down, Key::Right]);
+    assert_eq!(cursor(&app), Path::new("/link"));
+    assert_eq!(names(&app), ["four", "one", "three", "two"]);
+    assert_eq!(app.state().selected().unwrap().path, PathBuf::from("/link/four"));
+
+    press(&mut app, &[Key::Left]);
+    assert_eq!(selected(&app), "link");
+}
+
+#[test]
+fn rename_goes_through_the_vfs() {
+    let mut app = app();
+
+    // the prompt starts with the cursor before the extension
+    press(&mut app, &[Key::Up, Key::Char('r'), Key::Ctrl('u')]);
+    type_text(&mut app, "todo");
+    press(&mut app, &[Key::Enter]);
+
+    assert_eq!(names(&app), ["alpha", "beta", "gamma", "link", "todo.txt"]);
+    assert_eq!(selected(&app), "todo.txt");
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/todo.txt")).unwrap(), b"first\nsecond");
+    assert!(vfs.symlink_stat(Path::new("/notes.txt")).is_err());
+}
+
+#[test]
+fn delete_forever_removes_the_whole_tree() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Down, Key::Down, Key::Ctrl('d'), Key::Char('y')]);
+
+    assert_eq!(names(&app), ["alpha", "beta", "link", "notes.txt"]);
+    let vfs = app.state().vfs().unwrap();
+    assert!(vfs.symlink_stat(Path::new("/gamma/deep")).is_err());
+    assert!(app.state().dialog().is_none());
+}
+
+#[test]
+fn rename_refuses_an_existing_name() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Char('r'), Key::Ctrl('u')]);
+    type_text(&mut app, "beta");
+    press(&mut app, &[Key::Enter]);
+
+    // the prompt stays open with what was typed
+    assert!(app.state().dialog().is_some());
+    assert_eq!(names(&app), ["alpha", "beta", "gamma", "link", "notes.txt"]);
+    assert_eq!(app.state().selected().map(|item| item.name.as_os_str()), Some(OsStr::new("alpha")));
+}
+
+#[test]
+fn trash_and_undo_stay_in_memory() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Down, Key::Right, Key::Down, Key::Char('d')]);
+    assert_eq!(names(&app), ["four", "three", "two"]);
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(&trash_dir().join("files/one")).unwrap(), b"1");
+    let info = String::from_utf8(vfs.read(&trash_dir().join("info/one.trashinfo")).unwrap()).unwrap();
+    assert!(info.contains("Path=/beta/one\n"));
+
+    press(&mut app, &[Key::Char('u')]);
+    assert_eq!(names(&app), ["four", "one", "three", "two"]);
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/beta/one")).unwrap(), b"1");
+    assert_eq!(vfs.list(&trash_dir().join("files")).unwrap(), Vec::<PathBuf>::new());
+    assert_eq!(vfs.list(&trash_dir().join("info")).unwrap(), Vec::<PathBuf>::new());
+}
+
+#[test]
+fn paste_copies_a_tree_and_renames_on_conflict() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Down, Key::Char('y'), Key::Up, Key::Right, Key::Char('p')]);
+    finish_jobs(&mut app);
+    assert_eq!(cursor(&app), Path::new("/alpha"));
+    assert_eq!(names(&app), ["beta"]);
+
+    // a copy can be pasted again, the conflict is settled from the prompt
+    press(&mut app, &[Key::Char('p'), Key::Char('r')]);
+    finish_jobs(&mut app);
+    assert_eq!(names(&app), ["beta", "beta (1)"]);
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.list(Path::new("/alpha/beta (1)")).unwrap().len(), 4);
+    assert_eq!(vfs.read(Path::new("/alpha/beta/two")).unwrap(), b"2");
+    assert_eq!(vfs.read(Path::new("/beta/two")).unwrap(), b"2");
+}
+
+#[test]
+fn overwriting_paste_is_undone_and_redone() {
+    let mut app = app();
+    app.state().vfs().unwrap().write(Path::new("/alpha/notes.txt"), b"old").unwrap();
+
+    press(&mut app, &[Key::Up, Key::Char('y'), Key::Down, Key::Right, Key::Char('p'), Key::Char('o')]);
+    finish_jobs(&mut app);
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/alpha/notes.txt")).unwrap(), b"first\nsecond");
+    assert_eq!(vfs.read(&trash_dir().join("files/notes.txt")).unwrap(), b"old");
+
+    // the pasted copy has to go before the old file can come back
+    press(&mut app, &[Key::Char('u')]);
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/alpha/notes.txt")).unwrap(), b"old");
+
+    press(&mut app, &[Key::Ctrl('r')]);
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/alpha/notes.txt")).unwrap(), b"first\nsecond");
+    assert_eq!(names(&app), ["notes.txt"]);
+}
+
+#[test]
+fn cut_and_paste_moves_and_undo_moves_back() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Up, Key::Char('x'), Key::Down, Key::Down, Key::Down, Key::Right, Key::Char('p')]);
+    finish_jobs(&mut app);
+    assert_eq!(cursor(&app), Path::new("/gamma"));
+    assert_eq!(names(&app), ["deep", "notes.txt"]);
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/gamma/notes.txt")).unwrap(), b"first\nsecond");
+    assert!(vfs.symlink_stat(Path::new("/notes.txt")).is_err());
+
+    press(&mut app, &[Key::Char('u')]);
+    assert_eq!(names(&app), ["deep"]);
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/notes.txt")).unwrap(), b"first\nsecond");
+}
+
+#[test]
+fn cancelled_conflict_keeps_the_cut() {
+    let mut app = app();
+    app.state().vfs().unwrap().write(Path::new("/alpha/notes.txt"), b"old").unwrap();
+
+    press(&mut app, &[Key::Up, Key::Char('x'), Key::Down, Key::Right, Key::Char('p'), Key::Esc]);
+    finish_jobs(&mut app);
+    assert!(app.state().clipboard().is_some());
+
+    press(&mut app, &[Key::Char('p'), Key::Char('o')]);
+    finish_jobs(&mut app);
+    assert!(app.state().clipboard().is_none());
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/alpha/notes.txt")).unwrap(), b"first\nsecond");
+    assert!(vfs.symlink_stat(Path::new("/notes.txt")).is_err());
+}
+
+#[test]
+fn new_entries_are_created_with_their_parents_and_undone_together() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Right, Key::Char('n')]);
+    type_text(&mut app, "new/sub/file.txt");
+    press(&mut app, &[Key::Enter]);
+    assert_eq!(selected(&app), "new");
+
+    press(&mut app, &[Key::Char('N')]);
+    type_text(&mut app, "fresh");
+    press(&mut app, &[Key::Enter]);
+    assert_eq!(names(&app), ["fresh", "new"]);
+
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/alpha/new/sub/file.txt")).unwrap(), b"");
+    assert!(vfs.stat(Path::new("/alpha/fresh")).unwrap().is_dir());
+
+    press(&mut app, &[Key::Char('u')]);
+    assert_eq!(names(&app), ["new"]);
+
+    // the file goes to the trash, then the directories made for it are removed
+    press(&mut app, &[Key::Char('u')]);
+    assert_eq!(names(&app), Vec::<String>::new());
+
+    press(&mut app, &[Key::Ctrl('r')]);
+    let vfs = app.state().vfs().unwrap();
+    assert_eq!(vfs.read(Path::new("/alpha/new/sub/file.txt")).unwrap(), b"");
+}
+
+#[test]
+fn local_only_actions_are_refused() {
+    let mut app = app();
+
+    press(&mut app, &[Key::Up, Key::Char('z'), Key::Char('m'), Key::Char('X')]);
+
+    assert!(app.state().dialog().is_none());
+    assert_eq!(names(&app), ["alpha", "beta", "gamma", "link", "notes.txt"]);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use file_tui::inputs::key::Key;
+use tui::buffer::Buffer;
+use tui::style::Modifier;
+
+use common::{app, press, render, screen, type_text};
+
+const FIXTURE: &str = "
+    docs/
+    docs/guide.md = # Guide
+    src/
+    src/main.rs 0755 = fn main() {}
+    link -> docs
+    notes.txt 0600 @1700000000 = first\\nsecond
+";
+
+/// Rows of the list drawn in bold, which is how the selection shows
+fn bold_rows(buffer: &Buffer) -> Vec<u16> {
+    (0..buffer.area.height)
+        .filter(|y| buffer.get(3, *y).modifier.contains(Modifier::BOLD))
+        .collect()
+}
+
+#[test]
+fn list() {
+    let mut app = app(FIXTURE);
+
+    insta::assert_snapshot!(screen(&render(&mut app, &[], 80, 28)));
+}
+
+#[test]
+fn list_after_entering_a_directory() {
+    let mut app = app(FIXTURE);
+
+    insta::assert_snapshot!(screen(&render(&mut app, &[Key::Down, Key::Down, Key::Right], 80, 28)));
+}
+
+#[test]
+fn selection_follows_the_cursor() {
+    let mut app = app(FIXTURE);
+
+    // the list starts below the three rows of the title and its own border
+    assert_eq!(bold_rows(&render(&mut app, &[], 80, 28)), [4]);
+    assert_eq!(bold_rows(&render(&mut app, &[Key::Down, Key::Down], 80, 28)), [6]);
+    assert_eq!(bold_rows(&render(&mut app, &[Key::Up, Key::Up, Key::Up], 80, 28)), [7]);
+}
+
+#[test]
+fn help() {
+    let mut app = app(FIXTURE);
+
+    insta::assert_snapshot!(screen(&render(&mut app, &[Key::Char('?')], 52, 28)));
+}
+
+#[test]
+fn help_scrolled_to_the_end() {
+    let mut app = app(FIXTURE);
+
+    insta::assert_snapshot!(screen(&render(&mut app, &[Key::Char('?'), Key::End], 52, 28)));
+
+    // the arrows scroll the help, not the list behind it
+    let scrolled_back = screen(&render(&mut app, &[Key::Up, Key::Char('?')], 52, 28));
+    let list = screen(&render(&mut app, &[], 52, 28));
+    assert_eq!(scrolled_back, list);
+    assert!(screen(&render(&mut app, &[Key::Char('?')], 52, 28)).contains("<Ctrl+c> <q>"));
+}
+
+#[test]
+fn too_small() {
+    let mut app = app(FIXTURE);
+
+    insta::assert_snapshot!(screen(&render(&mut app, &[], 51, 28)));
+    assert!(screen(&render(&mut app, &[], 52, 27)).contains("Application need at least"));
+    assert!(!screen(&render(&mut app, &[], 52, 28)).contains("Application need at least"));
+}
+
+#[test]
+fn log_panel() {
+    let mut app = app(FIXTURE);
+
+    insta::assert_snapshot!(screen(&render(&mut app, &[Key::Char('D')], 80, 30)));
+}
+
+#[test]
+fn rename_prompt() {
+    let mut app = app(FIXTURE);
+
+    press(&mut app, &[Key::Up, Key::Char('r'), Key::Ctrl('u')]);
+    type_text(&mut app, "todo");
+    insta::assert_snapshot!(screen(&render(&mut app, &[], 80, 28)));
+}